@@ -1,5 +1,7 @@
 use std::process::Command;
 
+use crate::logging;
+
 #[derive(Debug, Clone)]
 pub struct AudioDevice {
     pub name: String,        // Internal PulseAudio name (e.g., alsa_input.pci-...)
@@ -8,10 +10,13 @@ pub struct AudioDevice {
 }
 
 pub fn get_audio_devices() -> Vec<AudioDevice> {
-    let output = Command::new("pactl")
-        .arg("list")
-        .arg("sources")
-        .output();
+    let mut cmd = Command::new("pactl");
+    cmd.arg("list").arg("sources");
+    logging::command(&cmd);
+    let output = cmd.output();
+    if let Ok(out) = &output {
+        logging::output("pactl", out);
+    }
 
     let mut devices = Vec::new();
 
@@ -0,0 +1,141 @@
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use log::info;
+use serde::Serialize;
+
+use crate::instance;
+use crate::output;
+use crate::logging;
+
+// Boundaries closer than this to the previous chapter are dropped, so a
+// double-clicked marker doesn't make an empty chapter
+const MIN_CHAPTER_LENGTH: f64 = 1.0;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Chapter {
+    pub start: f64, // seconds
+    pub end: f64,
+    pub title: String,
+}
+
+/// Chapters starting at each boundary, the first one at 0. A recording
+/// without any boundary gets no chapters at all.
+pub fn from_boundaries(boundaries: &[f64], total: f64) -> Vec<Chapter> {
+    let mut starts = vec![0.0];
+    for &start in boundaries {
+        if start - starts[starts.len() - 1] >= MIN_CHAPTER_LENGTH && total - start >= MIN_CHAPTER_LENGTH {
+            starts.push(start);
+        }
+    }
+    if starts.len() < 2 {
+        return Vec::new();
+    }
+
+    starts
+        .iter()
+        .enumerate()
+        .map(|(i, &start)| Chapter {
+            start,
+            end: starts.get(i + 1).copied().unwrap_or(total),
+            title: format!("Chapter {}", i + 1),
+        })
+        .collect()
+}
+
+/// "1:05" or "1:02:05", the format video sites pick up from descriptions
+pub fn format_timestamp(seconds: f64) -> String {
+    let total = seconds.max(0.0) as u64;
+    let (h, m, s) = (total / 3600, total / 60 % 60, total % 60);
+    if h > 0 {
+        format!("{}:{:02}:{:02}", h, m, s)
+    } else {
+        format!("{}:{:02}", m, s)
+    }
+}
+
+/// One "timestamp title" line per chapter
+pub fn timestamp_list(chapters: &[Chapter]) -> String {
+    chapters
+        .iter()
+        .map(|c| format!("{} {}\n", format_timestamp(c.start), c.title))
+        .collect()
+}
+
+// ffmetadata needs '=', ';', '#', '\' and newlines escaped
+fn escape(value: &str) -> String {
+    let mut escaped = String::new();
+    for c in value.chars() {
+        if "=;#\\\n".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn ffmetadata(tags: &[(String, String)], chapters: &[Chapter]) -> String {
+    let mut content = String::from(";FFMETADATA1\n");
+    for (key, value) in tags {
+        content.push_str(&format!("{}={}\n", escape(key), escape(value)));
+    }
+    for chapter in chapters {
+        content.push_str(&format!(
+            "[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+            (chapter.start * 1000.0) as u64,
+            (chapter.end * 1000.0) as u64,
+            escape(&chapter.title)
+        ));
+    }
+    content
+}
+
+/// Copy `input` to `output` with its chapters replaced by `chapters`. Unless
+/// `tags` is empty it also replaces the container tags.
+pub fn write(input: &str, tags: &[(String, String)], chapters: &[Chapter], output: &str) -> Result<(), String> {
+    info!("Writing {} tags and {} chapters to {}", tags.len(), chapters.len(), output);
+
+    let metadata_file = instance::temp_path("chapters.txt");
+    fs::write(&metadata_file, ffmetadata(tags, chapters)).map_err(|e| e.to_string())?;
+
+    let mut cmd = Command::new("ffmpeg");
+    cmd.arg("-i").arg(input)
+        .arg("-f").arg("ffmetadata")
+        .arg("-i").arg(&metadata_file)
+        .arg("-map").arg("0")
+        .arg("-map_metadata").arg(if tags.is_empty() { "0" } else { "1" })
+        .arg("-map_chapters").arg("1")
+        .arg("-c").arg("copy")
+        .arg("-y")
+        .arg(output)
+        .stdin(Stdio::null());
+    logging::command(&cmd);
+    let result = cmd.output().map_err(|e| format!("Failed to run ffmpeg: {}", e));
+    let _ = fs::remove_file(&metadata_file);
+    let result = result?;
+    logging::output("ffmpeg", &result);
+
+    if !result.status.success() {
+        return Err("FFmpeg could not write chapters and tags".to_string());
+    }
+    Ok(())
+}
+
+/// Rewrite the chapters of a finished recording in place
+pub fn update(path: &str, chapters: &[Chapter]) -> Result<(), String> {
+    let path = Path::new(path);
+    let name = path.file_name().ok_or("Invalid recording path")?.to_string_lossy();
+    // Same directory so the rename can't cross filesystems
+    let staged = path.with_file_name(format!(".{}.chapters.mp4", name));
+    let staged_str = staged.to_string_lossy().to_string();
+
+    if let Err(e) = write(&path.to_string_lossy(), &[], chapters, &staged_str) {
+        let _ = fs::remove_file(&staged);
+        return Err(e);
+    }
+    output::replace(&staged, path).map_err(|e| {
+        let _ = fs::remove_file(&staged);
+        e.to_string()
+    })
+}
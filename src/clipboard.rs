@@ -0,0 +1,63 @@
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+fn file_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for byte in path.to_string_lossy().bytes() {
+        // Percent-encode everything outside the unreserved set (keep '/')
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    uri
+}
+
+fn wait_wl_copy(mut child: std::process::Child) -> Result<(), String> {
+    // wl-copy forks into the background to serve the selection, so the
+    // foreground process exits as soon as it has read its input.
+    let status = child.wait().map_err(|e| e.to_string())?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("wl-copy exited with {}", status))
+    }
+}
+
+/// Put plain text on the regular clipboard
+pub fn copy_text(text: &str) -> Result<(), String> {
+    let mut child = Command::new("wl-copy")
+        .arg("--type").arg("text/plain")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Failed to run wl-copy: {}", e))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes()).map_err(|e| e.to_string())?;
+    }
+    wait_wl_copy(child)
+}
+
+/// Put a finished recording on the Wayland clipboard as a `text/uri-list`,
+/// what file managers and chat apps paste as an attachment. Only the path
+/// is handed over, so the file is never read into memory.
+pub fn copy_recording(path: &str) -> Result<(), String> {
+    let path = Path::new(path);
+    let mut child = Command::new("wl-copy")
+        .arg("--type").arg("text/uri-list")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Failed to run wl-copy: {}", e))?;
+
+    // Dropping stdin after writing closes the pipe so wl-copy sees EOF
+    if let Some(mut stdin) = child.stdin.take() {
+        write!(stdin, "{}\r\n", file_uri(path)).map_err(|e| e.to_string())?;
+    }
+    wait_wl_copy(child)
+}
@@ -0,0 +1,83 @@
+use std::fs;
+use std::process::{Command, Stdio};
+
+use log::info;
+
+use crate::instance;
+use crate::logging;
+use crate::overlay::{self, OverlayConfig};
+use crate::watermark::{self, WatermarkConfig, WatermarkContent};
+
+/// Burn the overlay (read from `overlay_input`) and the watermark into the
+/// video of `input` in a single re-encode. The watermark goes on top.
+pub fn apply(
+    input: &str,
+    overlay: Option<(&OverlayConfig, &str)>,
+    watermark: Option<&WatermarkConfig>,
+    output: &str,
+) -> Result<(), String> {
+    let mut cmd = Command::new("ffmpeg");
+    cmd.arg("-i").arg(input);
+
+    let mut inputs = 1;
+    let mut filters = Vec::new();
+    let mut base = "0:v".to_string();
+
+    if let Some((config, source)) = overlay {
+        info!("Compositing overlay {} onto {}", source, input);
+        cmd.args(overlay::input_args(config)).arg("-i").arg(source);
+        filters.push(overlay::filter(config, inputs, &base, "composited"));
+        inputs += 1;
+        base = "composited".to_string();
+    }
+
+    let text_file = instance::temp_path("watermark.txt");
+    if let Some(config) = watermark {
+        info!("Applying watermark to {}", input);
+        match &config.content {
+            WatermarkContent::Text { text, .. } => fs::write(&text_file, text).map_err(|e| e.to_string())?,
+            WatermarkContent::Image(path) => {
+                cmd.arg("-i").arg(path);
+            }
+        }
+        filters.push(watermark::filter(config, &base, "watermarked", inputs, &text_file));
+        base = "watermarked".to_string();
+    }
+
+    if filters.is_empty() {
+        return Err("Nothing to compose".to_string());
+    }
+
+    cmd.arg("-filter_complex").arg(filters.join(";"))
+        .arg("-map").arg(format!("[{}]", base))
+        .arg("-map").arg("0:a?")
+        .arg("-c:v").arg("libx264")
+        .arg("-preset").arg("veryfast")
+        .arg("-crf").arg("20")
+        .arg("-c:a").arg("copy")
+        .arg("-y")
+        .arg(output)
+        .stdin(Stdio::null());
+    logging::command(&cmd);
+    let result = cmd.output().map_err(|e| e.to_string());
+
+    let _ = fs::remove_file(&text_file);
+
+    let result = result?;
+    logging::output("ffmpeg", &result);
+    if !result.status.success() {
+        return Err(format!("FFmpeg failed: {}", last_line(&result.stderr)));
+    }
+    Ok(())
+}
+
+// ffmpeg prints the reason it gave up last
+fn last_line(stderr: &[u8]) -> String {
+    String::from_utf8_lossy(stderr)
+        .lines()
+        .rev()
+        .map(str::trim)
+        .find(|l| !l.is_empty())
+        .unwrap_or("no output")
+        .to_string()
+}
@@ -0,0 +1,218 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use serde_json::Value;
+
+use crate::geometry::Geometry;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compositor {
+    Sway,
+    Hyprland,
+}
+
+#[derive(Debug, Clone)]
+pub struct WindowInfo {
+    pub title: String,
+    pub app_id: String,
+    pub geometry: Geometry,
+}
+
+impl WindowInfo {
+    pub fn label(&self) -> String {
+        if self.app_id.is_empty() || self.title.contains(&self.app_id) {
+            self.title.clone()
+        } else {
+            format!("{} - {}", self.title, self.app_id)
+        }
+    }
+}
+
+/// Work out which compositor we are running under from its IPC environment
+pub fn detect() -> Option<Compositor> {
+    if std::env::var_os("SWAYSOCK").is_some() {
+        Some(Compositor::Sway)
+    } else if std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+        Some(Compositor::Hyprland)
+    } else {
+        None
+    }
+}
+
+fn run_json(program: &str, args: &[&str]) -> Result<Value, String> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+
+    if !output.status.success() {
+        return Err(format!("{} exited with {}", program, output.status));
+    }
+    serde_json::from_slice(&output.stdout).map_err(|e| format!("Invalid {} output: {}", program, e))
+}
+
+fn json_geometry(x: &Value, y: &Value, w: &Value, h: &Value) -> Option<Geometry> {
+    let geometry = format!("{},{} {}x{}", x.as_i64()?, y.as_i64()?, w.as_i64()?, h.as_i64()?);
+    geometry.parse().ok()
+}
+
+// `rect` covers the borders (and the title bar, depending on the layout),
+// `window_rect` is the client area relative to it. Recording that offset
+// area leaves the decorations out whatever the layout put in `deco_rect`.
+fn sway_content(node: &Value) -> Option<Geometry> {
+    let (rect, window) = (&node["rect"], &node["window_rect"]);
+    if window.is_null() {
+        return json_geometry(&rect["x"], &rect["y"], &rect["width"], &rect["height"]);
+    }
+    let x = rect["x"].as_i64()? + window["x"].as_i64()?;
+    let y = rect["y"].as_i64()? + window["y"].as_i64()?;
+    json_geometry(&x.into(), &y.into(), &window["width"], &window["height"])
+}
+
+// Walk sway's tree, collecting visible views (nodes that belong to a process)
+fn collect_sway_windows(node: &Value, windows: &mut Vec<WindowInfo>) {
+    let is_view = node.get("pid").is_some_and(|p| !p.is_null());
+    let visible = node.get("visible").and_then(|v| v.as_bool()).unwrap_or(false);
+
+    if is_view && visible {
+        if let Some(geometry) = sway_content(node) {
+            let app_id = node["app_id"].as_str()
+                .or_else(|| node["window_properties"]["class"].as_str())
+                .unwrap_or_default();
+            windows.push(WindowInfo {
+                title: node["name"].as_str().unwrap_or_default().to_string(),
+                app_id: app_id.to_string(),
+                geometry,
+            });
+        }
+    }
+
+    for key in ["nodes", "floating_nodes"] {
+        if let Some(children) = node.get(key).and_then(|c| c.as_array()) {
+            for child in children {
+                collect_sway_windows(child, windows);
+            }
+        }
+    }
+}
+
+fn hyprland_windows() -> Result<Vec<WindowInfo>, String> {
+    let clients = run_json("hyprctl", &["clients", "-j"])?;
+    let monitors = run_json("hyprctl", &["monitors", "-j"])?;
+
+    // Only windows on a currently shown workspace are on screen
+    let active_workspaces: Vec<i64> = monitors
+        .as_array()
+        .map(|m| m.iter().filter_map(|m| m["activeWorkspace"]["id"].as_i64()).collect())
+        .unwrap_or_default();
+
+    let windows = clients
+        .as_array()
+        .map(|clients| {
+            clients
+                .iter()
+                .filter(|c| c["mapped"].as_bool().unwrap_or(false) && !c["hidden"].as_bool().unwrap_or(false))
+                .filter(|c| c["workspace"]["id"].as_i64().is_some_and(|id| active_workspaces.contains(&id)))
+                .filter_map(|c| {
+                    let geometry = json_geometry(&c["at"][0], &c["at"][1], &c["size"][0], &c["size"][1])?;
+                    Some(WindowInfo {
+                        title: c["title"].as_str().unwrap_or_default().to_string(),
+                        app_id: c["class"].as_str().unwrap_or_default().to_string(),
+                        geometry,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(windows)
+}
+
+/// List the windows currently visible on screen
+pub fn list_windows() -> Result<Vec<WindowInfo>, String> {
+    match detect() {
+        Some(Compositor::Sway) => {
+            let tree = run_json("swaymsg", &["-t", "get_tree", "-r"])?;
+            let mut windows = Vec::new();
+            collect_sway_windows(&tree, &mut windows);
+            Ok(windows)
+        }
+        Some(Compositor::Hyprland) => hyprland_windows(),
+        None => Err("Window listing needs sway or Hyprland".to_string()),
+    }
+}
+
+/// Names of the active outputs, as wl-screenrec's `-o` expects them
+pub fn list_outputs() -> Result<Vec<String>, String> {
+    let outputs = match detect() {
+        Some(Compositor::Sway) => run_json("swaymsg", &["-t", "get_outputs", "-r"])?,
+        Some(Compositor::Hyprland) => run_json("hyprctl", &["monitors", "-j"])?,
+        None => return Err("Output listing needs sway or Hyprland".to_string()),
+    };
+
+    Ok(outputs
+        .as_array()
+        .map(|outputs| {
+            outputs
+                .iter()
+                .filter(|o| o["active"].as_bool().unwrap_or(true) && !o["disabled"].as_bool().unwrap_or(false))
+                .filter_map(|o| o["name"].as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+/// Run slurp with the window boxes pre-populated, so a click selects a window
+/// and a drag still selects a free-form area. Returns slurp's raw output.
+pub fn slurp_windows(windows: &[WindowInfo]) -> Result<Option<String>, String> {
+    let mut child = Command::new("slurp")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run slurp: {}", e))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        for window in windows {
+            // slurp reads one "X,Y WxH label" box per line
+            writeln!(stdin, "{} {}", window.geometry, window.label().replace('\n', " "))
+                .map_err(|e| e.to_string())?;
+        }
+    }
+
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        // Selection was cancelled
+        return Ok(None);
+    }
+    Ok(Some(String::from_utf8_lossy(&output.stdout).trim().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sway_windows_leave_out_decorations() {
+        let tree = serde_json::json!({
+            "nodes": [{
+                "pid": 42,
+                "visible": true,
+                "name": "Terminal",
+                "app_id": "foot",
+                "rect": { "x": 100, "y": 50, "width": 804, "height": 602 },
+                "window_rect": { "x": 2, "y": 26, "width": 800, "height": 574 },
+                "deco_rect": { "x": 0, "y": 0, "width": 804, "height": 24 },
+            }, {
+                "pid": 43,
+                "visible": false,
+                "rect": { "x": 0, "y": 0, "width": 10, "height": 10 },
+            }],
+        });
+        let mut windows = Vec::new();
+        collect_sway_windows(&tree, &mut windows);
+
+        assert_eq!(windows.len(), 1);
+        assert_eq!(windows[0].geometry.to_string(), "102,76 800x574");
+        assert_eq!(windows[0].app_id, "foot");
+    }
+}
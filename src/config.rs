@@ -1,56 +1,397 @@
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use directories::ProjectDirs;
+use log::{error, info, warn};
+
+/// Schema version written to config.json. Bump it and add a migration
+/// whenever a field is renamed or its meaning changes.
+pub const CURRENT_VERSION: u32 = 2;
+
+pub const DEFAULT_PROFILE: &str = "Default";
+
+// MIGRATIONS[n] upgrades a version n file to version n + 1. Files written
+// before versioning have no "version" key and count as version 0.
+const MIGRATIONS: &[fn(&mut Map<String, Value>)] = &[migrate_v0_to_v1, migrate_v1_to_v2];
+
+fn migrate_v0_to_v1(map: &mut Map<String, Value>) {
+    // The UI placeholder "~/Videos" could end up saved verbatim, but nothing
+    // expands "~" when the recording path is built
+    if let Some(Value::String(path)) = map.get_mut("save_path") {
+        if let (Some(rest), Some(user_dirs)) = (path.strip_prefix('~'), directories::UserDirs::new()) {
+            *path = format!("{}{}", user_dirs.home_dir().to_string_lossy(), rest);
+        }
+    }
+}
+
+fn migrate_v1_to_v2(map: &mut Map<String, Value>) {
+    // Settings used to be one flat object; it becomes the first profile
+    let mut profile = std::mem::take(map);
+    profile.remove("version");
+
+    let mut profiles = Map::new();
+    profiles.insert(DEFAULT_PROFILE.to_string(), Value::Object(profile));
+    map.insert("active_profile".to_string(), DEFAULT_PROFILE.into());
+    map.insert("profiles".to_string(), Value::Object(profiles));
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RegionPreset {
+    pub name: String,
+    pub geometry: String, // slurp's "X,Y WxH" format
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Settings {
+    #[serde(default = "default_save_path")]
     pub save_path: String,
+    #[serde(default)]
+    pub work_dir: String, // segments go here, empty for a hidden folder in save_path
+    #[serde(default = "default_min_free_mb")]
+    pub min_free_mb: u64, // free space the working folder needs to start recording
+    #[serde(default = "default_audio_mode")]
     pub audio_mode: String,
+    #[serde(default)]
+    pub auto_copy: bool,
+    #[serde(default)]
+    pub metadata_sidecar: bool, // recording details as JSON next to the video
+    #[serde(default)]
+    pub separate_tracks: bool,
+    #[serde(default)]
+    pub noise_suppression: bool,
+    #[serde(default)]
+    pub loudnorm: bool,
+    #[serde(default = "default_loudness_target")]
+    pub loudness_target: f32,
+    #[serde(default)]
+    pub overlay_enabled: bool,
+    #[serde(default)]
+    pub overlay_source: String,
+    #[serde(default = "default_overlay_corner")]
+    pub overlay_corner: String,
+    #[serde(default = "default_overlay_size")]
+    pub overlay_size: u32,
+    #[serde(default)]
+    pub watermark_enabled: bool,
+    #[serde(default = "default_watermark_kind")]
+    pub watermark_kind: String,
+    #[serde(default)]
+    pub watermark_text: String,
+    #[serde(default)]
+    pub watermark_image: String,
+    #[serde(default = "default_watermark_font")]
+    pub watermark_font: String,
+    #[serde(default = "default_watermark_font_size")]
+    pub watermark_font_size: u32,
+    #[serde(default = "default_overlay_corner")]
+    pub watermark_position: String,
+    #[serde(default = "default_watermark_opacity")]
+    pub watermark_opacity: u32,
+    #[serde(default)]
+    pub region_presets: Vec<RegionPreset>,
+    #[serde(default = "default_region_constraint")]
+    pub region_constraint: String, // "Free", an aspect ratio like "16:9" or a size like "1280x720"
+    #[serde(default = "default_volume")]
+    pub mic_volume: u32,
+    #[serde(default = "default_volume")]
+    pub monitor_volume: u32,
+    #[serde(default = "default_recording_mode")]
+    pub recording_mode: String, // "fullscreen", "selection" or "window"
+    #[serde(default)]
+    pub recording_geometry: String,
+    #[serde(default)]
+    pub recording_output: String, // output for full screen recordings, empty for the default
+    // Keys this version doesn't know about, kept so saving doesn't drop them
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+fn default_save_path() -> String {
+    match directories::UserDirs::new() {
+        Some(user_dirs) => user_dirs
+            .video_dir()
+            .unwrap_or(user_dirs.home_dir())
+            .to_string_lossy()
+            .to_string(),
+        None => String::new(),
+    }
+}
+
+fn default_recording_mode() -> String {
+    "fullscreen".to_string()
+}
+
+fn default_audio_mode() -> String {
+    "Mute".to_string() // Matches Slint UI default
+}
+
+fn default_volume() -> u32 {
+    100
+}
+
+fn default_loudness_target() -> f32 {
+    -16.0
+}
+
+fn default_overlay_corner() -> String {
+    "bottom-right".to_string()
+}
+
+fn default_overlay_size() -> u32 {
+    25
+}
+
+fn default_region_constraint() -> String {
+    "Free".to_string()
+}
+
+fn default_watermark_kind() -> String {
+    "text".to_string()
+}
+
+fn default_min_free_mb() -> u64 {
+    2048
+}
+
+fn default_watermark_font() -> String {
+    "Sans".to_string()
+}
+
+fn default_watermark_font_size() -> u32 {
+    32
+}
+
+fn default_watermark_opacity() -> u32 {
+    70
 }
 
 impl Default for Settings {
     fn default() -> Self {
-        let mut save_path = String::new();
-        if let Some(user_dirs) = directories::UserDirs::new() {
-            if let Some(video_dir) = user_dirs.video_dir() {
-                save_path = video_dir.to_string_lossy().to_string();
+        Self {
+            save_path: default_save_path(),
+            work_dir: String::new(),
+            min_free_mb: default_min_free_mb(),
+            audio_mode: default_audio_mode(),
+            auto_copy: false,
+            metadata_sidecar: false,
+            separate_tracks: false,
+            noise_suppression: false,
+            loudnorm: false,
+            loudness_target: default_loudness_target(),
+            overlay_enabled: false,
+            overlay_source: String::new(),
+            overlay_corner: default_overlay_corner(),
+            overlay_size: default_overlay_size(),
+            watermark_enabled: false,
+            watermark_kind: default_watermark_kind(),
+            watermark_text: String::new(),
+            watermark_image: String::new(),
+            watermark_font: default_watermark_font(),
+            watermark_font_size: default_watermark_font_size(),
+            watermark_position: default_overlay_corner(),
+            watermark_opacity: default_watermark_opacity(),
+            region_presets: Vec::new(),
+            region_constraint: default_region_constraint(),
+            mic_volume: default_volume(),
+            monitor_volume: default_volume(),
+            recording_mode: default_recording_mode(),
+            recording_geometry: String::new(),
+            recording_output: String::new(),
+            extra: Map::new(),
+        }
+    }
+}
+
+impl Settings {
+    /// Reset values that parse but aren't valid choices
+    pub fn validate(&mut self) {
+        let valid_modes = ["Mute", "Screen", "Mic", "Both"];
+        if !valid_modes.contains(&self.audio_mode.as_str()) {
+            self.audio_mode = default_audio_mode();
+        }
+        let valid_corners = ["top-left", "top-right", "bottom-left", "bottom-right"];
+        if !valid_corners.contains(&self.overlay_corner.as_str()) {
+            self.overlay_corner = default_overlay_corner();
+        }
+        if !valid_corners.contains(&self.watermark_position.as_str()) {
+            self.watermark_position = default_overlay_corner();
+        }
+        if self.region_constraint.parse::<crate::geometry::SizeConstraint>().is_err() {
+            self.region_constraint = default_region_constraint();
+        }
+        if self.watermark_kind != "text" && self.watermark_kind != "image" {
+            self.watermark_kind = default_watermark_kind();
+        }
+        let valid_recording_modes = ["fullscreen", "selection", "window"];
+        if !valid_recording_modes.contains(&self.recording_mode.as_str())
+            || (self.recording_mode != "fullscreen" && self.recording_geometry.parse::<crate::geometry::Geometry>().is_err())
+        {
+            self.recording_mode = default_recording_mode();
+            self.recording_geometry = String::new();
+        }
+    }
+
+    // Deserialize field by field, so one bad value only resets that field
+    fn from_map(map: Map<String, Value>) -> Self {
+        if let Ok(settings) = serde_json::from_value(Value::Object(map.clone())) {
+            return settings;
+        }
+
+        let defaults = match serde_json::to_value(Self::default()) {
+            Ok(Value::Object(defaults)) => defaults,
+            _ => return Self::default(),
+        };
+        let mut valid = Map::new();
+        for (key, value) in map {
+            let mut probe = defaults.clone();
+            probe.insert(key.clone(), value.clone());
+            if serde_json::from_value::<Self>(Value::Object(probe)).is_ok() {
+                valid.insert(key, value);
             } else {
-                save_path = user_dirs.home_dir().to_string_lossy().to_string();
+                warn!("Ignoring invalid setting {}: {}", key, value);
             }
         }
+        serde_json::from_value(Value::Object(valid)).unwrap_or_default()
+    }
+
+    /// Effective settings of the active profile
+    pub fn load() -> Self {
+        SettingsFile::load().effective()
+    }
+
+    /// Store these settings as the active profile, leaving pinned values as they were
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut file = SettingsFile::load();
+        let stored = crate::layers::unapply(self, file.active());
+        file.profiles.insert(file.active_profile.clone(), stored);
+        file.save()
+    }
+}
+
+/// Everything in config.json: the named profiles and which one is in use
+#[derive(Serialize, Debug, Clone)]
+pub struct SettingsFile {
+    pub version: u32,
+    pub active_profile: String,
+    pub profiles: BTreeMap<String, Settings>,
+    // Keys this version doesn't know about, kept so saving doesn't drop them
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
 
+impl Default for SettingsFile {
+    fn default() -> Self {
+        let mut profiles = BTreeMap::new();
+        profiles.insert(DEFAULT_PROFILE.to_string(), Settings::default());
         Self {
-            save_path,
-            audio_mode: "Mute".to_string(), // Matches Slint UI default
+            version: CURRENT_VERSION,
+            active_profile: DEFAULT_PROFILE.to_string(),
+            profiles,
+            extra: Map::new(),
         }
     }
 }
 
-impl Settings {
-    fn get_config_path() -> Option<PathBuf> {
-        ProjectDirs::from("com", "ferdinankurnian", "roton")
-            .map(|proj_dirs| proj_dirs.config_dir().join("config.json"))
+fn get_config_path() -> Option<PathBuf> {
+    ProjectDirs::from("com", "ferdinankurnian", "roton")
+        .map(|proj_dirs| proj_dirs.config_dir().join("config.json"))
+}
+
+// Keep a copy of the file before it is rewritten, e.g. config.json.v0.bak
+fn backup(path: &Path, tag: &str) {
+    let backup = path.with_extension(format!("json.{}.bak", tag));
+    match fs::copy(path, &backup) {
+        Ok(_) => info!("Backed up settings to {}", backup.display()),
+        Err(e) => error!("Error backing up settings: {}", e),
     }
+}
 
-    pub fn load() -> Self {
-        if let Some(path) = Self::get_config_path() {
-            if let Ok(content) = fs::read_to_string(path) {
-                if let Ok(mut settings) = serde_json::from_str::<Self>(&content) {
-                    // Validate audio_mode
-                    let valid_modes = ["Mute", "Screen", "Mic", "Both"];
-                    if !valid_modes.contains(&settings.audio_mode.as_str()) {
-                        settings.audio_mode = "Mute".to_string();
+impl SettingsFile {
+    // Build from an up-to-date map, keeping every profile that can be read
+    fn from_map(mut map: Map<String, Value>) -> Self {
+        let version = map.remove("version").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+        let profiles = match map.remove("profiles") {
+            Some(Value::Object(profiles)) => profiles
+                .into_iter()
+                .filter_map(|(name, profile)| match profile {
+                    Value::Object(profile) => {
+                        let mut settings = Settings::from_map(profile);
+                        settings.validate();
+                        Some((name, settings))
+                    }
+                    _ => {
+                        warn!("Ignoring invalid profile {}", name);
+                        None
                     }
-                    return settings;
-                }
+                })
+                .collect(),
+            _ => BTreeMap::new(),
+        };
+        let active_profile = match map.remove("active_profile") {
+            Some(Value::String(name)) => name,
+            _ => DEFAULT_PROFILE.to_string(),
+        };
+
+        let mut file = Self { version, active_profile, profiles, extra: map };
+        if file.profiles.is_empty() {
+            file.profiles.insert(DEFAULT_PROFILE.to_string(), Settings::default());
+        }
+        if !file.profiles.contains_key(&file.active_profile) {
+            file.active_profile = file.profiles.keys().next().cloned().unwrap_or_default();
+        }
+        file
+    }
+
+    // Parse settings JSON written by this or any older version, migrating it
+    // to the current schema. Also returns the version it was written with.
+    fn parse(content: &str) -> Result<(Self, u32), String> {
+        let mut map = match serde_json::from_str::<Value>(content) {
+            Ok(Value::Object(map)) => map,
+            Ok(_) => return Err("not a JSON object".to_string()),
+            Err(e) => return Err(e.to_string()),
+        };
+
+        let version = map.get("version").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+        if version > CURRENT_VERSION {
+            warn!("Settings were written by a newer Roton (version {}), unknown fields are kept as-is", version);
+        }
+        if version < CURRENT_VERSION {
+            for migration in &MIGRATIONS[version as usize..] {
+                migration(&mut map);
             }
+            map.insert("version".to_string(), CURRENT_VERSION.into());
         }
-        Self::default()
+        Ok((Self::from_map(map), version))
+    }
+
+    pub fn load() -> Self {
+        let Some(path) = get_config_path() else { return Self::default() };
+        let Ok(content) = fs::read_to_string(&path) else { return Self::default() };
+
+        let (file, version) = match Self::parse(&content) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                // The next save overwrites the file, so keep what was there
+                warn!("Settings file {} is invalid ({}), using defaults", path.display(), e);
+                backup(&path, "invalid");
+                return Self::default();
+            }
+        };
+
+        if version < CURRENT_VERSION {
+            backup(&path, &format!("v{}", version));
+            info!("Migrated settings from version {} to {}", version, CURRENT_VERSION);
+            if let Err(e) = file.save() {
+                error!("Error saving migrated settings: {}", e);
+            }
+        }
+        file
     }
 
     pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
-        if let Some(path) = Self::get_config_path() {
+        if let Some(path) = get_config_path() {
             if let Some(parent) = path.parent() {
                 fs::create_dir_all(parent)?;
             }
@@ -59,4 +400,174 @@ impl Settings {
         }
         Ok(())
     }
+
+    /// The active profile as stored in config.json
+    pub fn active(&self) -> &Settings {
+        &self.profiles[&self.active_profile]
+    }
+
+    /// The active profile with environment and command-line overrides applied
+    pub fn effective(&self) -> Settings {
+        crate::layers::apply(self.active())
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.profiles.keys().cloned().collect()
+    }
+
+    pub fn switch(&mut self, name: &str) -> bool {
+        if self.profiles.contains_key(name) {
+            self.active_profile = name.to_string();
+            true
+        } else {
+            false
+        }
+    }
+
+    // "Demo", then "Demo (2)", "Demo (3)", ... if the name is taken
+    fn unique_name(&self, name: &str) -> String {
+        let name = name.trim();
+        let name = if name.is_empty() { DEFAULT_PROFILE } else { name };
+        (1..)
+            .map(|n| if n == 1 { name.to_string() } else { format!("{} ({})", name, n) })
+            .find(|candidate| !self.profiles.contains_key(candidate))
+            .unwrap_or_default()
+    }
+
+    /// Copy the active profile under a new name and switch to it
+    pub fn create(&mut self, name: &str) -> String {
+        let name = self.unique_name(name);
+        self.profiles.insert(name.clone(), self.active().clone());
+        self.active_profile = name.clone();
+        name
+    }
+
+    /// Remove the active profile. The last remaining one can't be deleted.
+    pub fn delete_active(&mut self) -> bool {
+        if self.profiles.len() < 2 {
+            return false;
+        }
+        self.profiles.remove(&self.active_profile);
+        self.active_profile = self.profiles.keys().next().cloned().unwrap_or_default();
+        true
+    }
+
+    /// Write the active profile to `path` as a settings file of its own
+    pub fn export_active(&self, path: &Path) -> Result<(), String> {
+        let mut profiles = BTreeMap::new();
+        profiles.insert(self.active_profile.clone(), self.active().clone());
+        let export = Self {
+            version: CURRENT_VERSION,
+            active_profile: self.active_profile.clone(),
+            profiles,
+            extra: Map::new(),
+        };
+
+        let content = serde_json::to_string_pretty(&export).map_err(|e| e.to_string())?;
+        fs::write(path, content).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    /// Add the profiles from an exported file (or a whole config.json) and
+    /// switch to the first one. Returns the names they were added under.
+    pub fn import(&mut self, path: &Path) -> Result<Vec<String>, String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let (imported, _) = Self::parse(&content).map_err(|e| format!("Invalid profile file {}: {}", path.display(), e))?;
+
+        let mut names = Vec::new();
+        for (name, mut settings) in imported.profiles {
+            // Save folders from someone else's machine usually don't exist here
+            if !Path::new(&settings.save_path).is_dir() {
+                settings.save_path = default_save_path();
+            }
+            if !settings.work_dir.is_empty() && !Path::new(&settings.work_dir).is_dir() {
+                settings.work_dir = String::new();
+            }
+            let name = self.unique_name(&name);
+            self.profiles.insert(name.clone(), settings);
+            names.push(name);
+        }
+
+        if let Some(first) = names.first() {
+            self.active_profile = first.clone();
+        }
+        Ok(names)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A flat settings file from before versioning and profiles
+    const FLAT_V0: &str = r#"{
+        "save_path": "/srv/recordings",
+        "audio_mode": "Mic",
+        "auto_copy": true
+    }"#;
+
+    #[test]
+    fn migrates_flat_file_into_default_profile() {
+        let (file, version) = SettingsFile::parse(FLAT_V0).unwrap();
+        assert_eq!(version, 0);
+        assert_eq!(file.version, CURRENT_VERSION);
+        assert_eq!(file.active_profile, DEFAULT_PROFILE);
+        assert_eq!(file.profiles.len(), 1);
+
+        let settings = file.active();
+        assert_eq!(settings.save_path, "/srv/recordings");
+        assert_eq!(settings.audio_mode, "Mic");
+        assert!(settings.auto_copy);
+        assert_eq!(settings.overlay_size, default_overlay_size());
+    }
+
+    #[test]
+    fn bad_field_keeps_the_others() {
+        let content = r#"{
+            "save_path": "/srv/recordings",
+            "audio_mode": "Both",
+            "overlay_size": "large",
+            "loudness_target": null
+        }"#;
+        let (file, _) = SettingsFile::parse(content).unwrap();
+        let settings = file.active();
+        assert_eq!(settings.save_path, "/srv/recordings");
+        assert_eq!(settings.audio_mode, "Both");
+        assert_eq!(settings.overlay_size, default_overlay_size());
+        assert_eq!(settings.loudness_target, default_loudness_target());
+    }
+
+    #[test]
+    fn invalid_choice_is_reset() {
+        let (file, _) = SettingsFile::parse(r#"{ "save_path": "/srv/recordings", "audio_mode": "Loud" }"#).unwrap();
+        assert_eq!(file.active().audio_mode, default_audio_mode());
+        assert_eq!(file.active().save_path, "/srv/recordings");
+    }
+
+    #[test]
+    fn unknown_keys_round_trip() {
+        let content = r#"{
+            "version": 2,
+            "active_profile": "Work",
+            "sync_token": "abc",
+            "profiles": {
+                "Work": { "save_path": "/srv/work", "future_option": [1, 2] }
+            }
+        }"#;
+        let (file, version) = SettingsFile::parse(content).unwrap();
+        assert_eq!(version, 2);
+        assert_eq!(file.extra.get("sync_token"), Some(&Value::from("abc")));
+        assert_eq!(file.active().extra.get("future_option"), Some(&serde_json::json!([1, 2])));
+
+        let saved = serde_json::to_string(&file).unwrap();
+        let (reloaded, _) = SettingsFile::parse(&saved).unwrap();
+        assert_eq!(reloaded.extra, file.extra);
+        assert_eq!(reloaded.active().extra, file.active().extra);
+        assert_eq!(reloaded.active().save_path, "/srv/work");
+    }
+
+    #[test]
+    fn rejects_non_objects() {
+        assert!(SettingsFile::parse("[]").is_err());
+        assert!(SettingsFile::parse("not json").is_err());
+    }
 }
@@ -0,0 +1,189 @@
+use std::fmt::Write;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::config::SettingsFile;
+use crate::layers;
+use crate::logging;
+
+// How much of the log goes into a bug report bundle
+const BUNDLE_LOG_BYTES: u64 = 64 * 1024;
+
+struct Tool {
+    name: &'static str,
+    version_args: &'static [&'static str],
+    required: bool,
+    // What stops working when the tool is missing
+    features: &'static str,
+}
+
+const TOOLS: &[Tool] = &[
+    Tool { name: "wl-screenrec", version_args: &["--version"], required: true, features: "All recording" },
+    Tool { name: "pactl", version_args: &["--version"], required: false, features: "Audio device list, Both mode" },
+    Tool { name: "slurp", version_args: &["-v"], required: false, features: "Select area, pick window on screen" },
+    Tool { name: "ffmpeg", version_args: &["-version"], required: false, features: "Pause, thumbnails, separate tracks, overlays, watermark, audio filters" },
+    Tool { name: "ffprobe", version_args: &["-version"], required: false, features: "Joining segments that differ after a pause" },
+    Tool { name: "parec", version_args: &["--version"], required: false, features: "Audio level meters" },
+    Tool { name: "wl-copy", version_args: &["--version"], required: false, features: "Copy to clipboard" },
+    Tool { name: "swaymsg", version_args: &["--version"], required: false, features: "Window list on sway" },
+    Tool { name: "hyprctl", version_args: &["version"], required: false, features: "Window list on Hyprland" },
+    Tool { name: "xdg-open", version_args: &["--version"], required: false, features: "Open folder" },
+];
+
+#[derive(Debug, Clone)]
+pub struct ToolStatus {
+    pub name: &'static str,
+    pub path: Option<PathBuf>,
+    pub version: Option<String>,
+    pub required: bool,
+    pub features: &'static str,
+}
+
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub tools: Vec<ToolStatus>,
+    pub audio_server: Result<String, String>,
+}
+
+impl Report {
+    pub fn has(&self, name: &str) -> bool {
+        self.tools.iter().any(|t| t.name == name && t.path.is_some())
+    }
+}
+
+fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+/// Resolve a command on PATH the way the shell would, without spawning `which`
+pub fn find_in_path(name: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(name))
+        .find(|candidate| is_executable(candidate))
+}
+
+// First non-empty line the tool prints for its version flag
+fn query_version(path: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new(path)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .ok()?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    stdout
+        .lines()
+        .chain(stderr.lines())
+        .map(|l| l.trim())
+        .find(|l| !l.is_empty())
+        .map(|l| l.to_string())
+}
+
+// Ask the PulseAudio (or pipewire-pulse) server who it is
+fn check_audio_server(pactl: Option<&Path>) -> Result<String, String> {
+    let pactl = pactl.ok_or("pactl not found")?;
+    let output = Command::new(pactl)
+        .arg("info")
+        .output()
+        .map_err(|e| format!("Failed to run pactl: {}", e))?;
+
+    if !output.status.success() {
+        return Err("Audio server is not reachable".to_string());
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let name = stdout
+        .lines()
+        .find_map(|l| l.trim().strip_prefix("Server Name:"))
+        .map(|n| n.trim().to_string())
+        .unwrap_or_else(|| "Unknown server".to_string());
+    Ok(name)
+}
+
+pub fn run() -> Report {
+    let tools: Vec<ToolStatus> = TOOLS
+        .iter()
+        .map(|tool| {
+            let path = find_in_path(tool.name);
+            let version = path.as_deref().and_then(|p| query_version(p, tool.version_args));
+            ToolStatus {
+                name: tool.name,
+                path,
+                version,
+                required: tool.required,
+                features: tool.features,
+            }
+        })
+        .collect();
+
+    let pactl = tools.iter().find(|t| t.name == "pactl").and_then(|t| t.path.clone());
+    let audio_server = check_audio_server(pactl.as_deref());
+
+    Report { tools, audio_server }
+}
+
+fn os_name() -> String {
+    fs::read_to_string("/etc/os-release")
+        .ok()
+        .and_then(|release| {
+            release
+                .lines()
+                .find_map(|l| l.strip_prefix("PRETTY_NAME="))
+                .map(|name| name.trim_matches('"').to_string())
+        })
+        .unwrap_or_else(|| "Unknown".to_string())
+}
+
+/// Plain-text bug report with versions, tool status, settings and the recent log
+pub fn bundle(report: &Report) -> String {
+    let env = |name: &str| std::env::var(name).unwrap_or_else(|_| "-".to_string());
+    let mut out = String::new();
+
+    let _ = writeln!(out, "# Roton diagnostics");
+    let _ = writeln!(out, "Roton: {}", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(out, "Generated: {}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"));
+    let _ = writeln!(out, "OS: {}", os_name());
+    let _ = writeln!(out, "Desktop: {}", env("XDG_CURRENT_DESKTOP"));
+    let _ = writeln!(out, "Wayland display: {}", env("WAYLAND_DISPLAY"));
+
+    let _ = writeln!(out, "\n## Tools");
+    for tool in &report.tools {
+        match &tool.path {
+            Some(path) => {
+                let version = tool.version.as_deref().unwrap_or("unknown version");
+                let _ = writeln!(out, "{}: {} ({})", tool.name, version, path.display());
+            }
+            None => {
+                let _ = writeln!(out, "{}: not found", tool.name);
+            }
+        }
+    }
+    let _ = match &report.audio_server {
+        Ok(name) => writeln!(out, "Audio server: {}", name),
+        Err(e) => writeln!(out, "Audio server: {}", e),
+    };
+
+    let _ = writeln!(out, "\n## Settings");
+    let file = SettingsFile::load();
+    let settings = serde_json::to_string_pretty(&file).unwrap_or_default();
+    let _ = writeln!(out, "{}", settings);
+
+    let _ = writeln!(out, "\n## Effective settings ({})", file.active_profile);
+    for setting in layers::effective(file.active()) {
+        let origin = setting.origin.map(|o| format!(" ({})", o)).unwrap_or_default();
+        let _ = writeln!(out, "{} = {}  [{}{}]", setting.key, setting.value, setting.source, origin);
+    }
+
+    let _ = writeln!(out, "\n## Log");
+    if let Some(path) = logging::log_path() {
+        let _ = writeln!(out, "({})", path.display());
+    }
+    out.push_str(&logging::tail(BUNDLE_LOG_BYTES));
+    out
+}
@@ -0,0 +1,64 @@
+use std::path::{Path, PathBuf};
+
+/// Margins kept on top of what finishing the recording needs: below the
+/// first the recording page warns, below the second the recording pauses
+pub const WARN_MARGIN: u64 = 1024 * 1024 * 1024;
+pub const PAUSE_MARGIN: u64 = 256 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpaceLevel {
+    Ok,
+    Low,
+    Critical,
+}
+
+/// Free space on the working directory's filesystem and how much of it
+/// finishing the recording takes, about the size of what is recorded so far
+#[derive(Debug, Clone, Copy)]
+pub struct DiskUsage {
+    pub available: u64,
+    pub reserved: u64,
+}
+
+impl DiskUsage {
+    pub fn level(&self) -> SpaceLevel {
+        if self.available < self.reserved + PAUSE_MARGIN {
+            SpaceLevel::Critical
+        } else if self.available < self.reserved + WARN_MARGIN {
+            SpaceLevel::Low
+        } else {
+            SpaceLevel::Ok
+        }
+    }
+}
+
+/// Where segments are recorded when no working directory is set: a hidden
+/// folder next to the recordings, so finishing is a rename and not a copy
+/// out of a small RAM-backed /tmp
+pub fn work_dir(configured: &str, save_path: &str) -> PathBuf {
+    if configured.is_empty() {
+        Path::new(save_path).join(".roton")
+    } else {
+        PathBuf::from(configured)
+    }
+}
+
+/// Bytes available to unprivileged users on the filesystem holding `path`,
+/// which doesn't have to exist yet
+pub fn available(path: &Path) -> Result<u64, String> {
+    let existing = path.ancestors().find(|p| p.exists()).unwrap_or(Path::new("/"));
+    // A single syscall, cheap enough for the recording page's timer
+    let stat = rustix::fs::statvfs(existing)
+        .map_err(|e| format!("Failed to check free space on {}: {}", existing.display(), e))?;
+    Ok(stat.f_bavail.saturating_mul(stat.f_frsize))
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const GB: f64 = 1024.0 * 1024.0 * 1024.0;
+    const MB: f64 = 1024.0 * 1024.0;
+    if bytes as f64 >= GB {
+        format!("{:.1} GB", bytes as f64 / GB)
+    } else {
+        format!("{:.0} MB", bytes as f64 / MB)
+    }
+}
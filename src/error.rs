@@ -0,0 +1,104 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::disk;
+
+#[derive(Debug)]
+pub enum RecorderError {
+    /// A required command is not installed
+    MissingTool(String),
+    /// A command exists but could not be started
+    Spawn { program: String, source: io::Error },
+    /// `pactl load-module` failed while setting up audio
+    AudioModule { module: String, stderr: String },
+    /// ffmpeg could not join the recorded segments
+    Concat { stderr: String },
+    /// Any other ffmpeg step needed to produce the file
+    Ffmpeg { step: String, stderr: String },
+    /// An operation was requested without an active session
+    NoSession,
+    /// The capture source can only change between segments
+    NotPaused,
+    /// "Both" needs a mic and a monitor, either for the mixer or for two tracks
+    MissingDevice(String),
+    /// Not enough free space in the working directory to start
+    LowDiskSpace { path: PathBuf, available: u64, required: u64 },
+    /// The session was stopped before anything was recorded
+    NoSegments,
+    Io(io::Error),
+}
+
+impl RecorderError {
+    /// Turn a failed `Command::spawn` into the most helpful variant
+    pub fn spawn(program: &str, source: io::Error) -> Self {
+        if source.kind() == io::ErrorKind::NotFound {
+            Self::MissingTool(program.to_string())
+        } else {
+            Self::Spawn { program: program.to_string(), source }
+        }
+    }
+
+    /// Short suggestion shown under the error message in the UI
+    pub fn hint(&self) -> String {
+        match self {
+            Self::MissingTool(tool) => format!("Install {} and make sure it is on your PATH. The diagnostics page lists every dependency.", tool),
+            Self::Spawn { program, .. } => format!("Check that {} is executable and runs from a terminal.", program),
+            Self::AudioModule { .. } => "Make sure PulseAudio or pipewire-pulse is running and the selected devices still exist, then refresh the device list.".to_string(),
+            Self::Concat { .. } => "The recorded segments are kept in the working folder. Try joining them manually with ffmpeg's concat demuxer.".to_string(),
+            Self::Ffmpeg { .. } => "Check that your ffmpeg build includes the needed encoders and filters.".to_string(),
+            Self::NoSession => "Start a new recording from the home page.".to_string(),
+            Self::LowDiskSpace { .. } => "Free up some space, or pick a working folder on another disk in the settings.".to_string(),
+            Self::NotPaused => "Pause the recording before changing what it captures.".to_string(),
+            Self::MissingDevice(device) => format!("Pick a {} in the settings, or refresh the device list if it isn't listed.", device),
+            Self::NoSegments => "Nothing was recorded. Check that wl-screenrec can capture your screen.".to_string(),
+            Self::Io(_) => "Check that the save folder exists and has free space.".to_string(),
+        }
+    }
+}
+
+// Keep only the tail of a tool's stderr, where the actual error usually is
+fn tail(stderr: &str) -> String {
+    let lines: Vec<&str> = stderr.lines().filter(|l| !l.trim().is_empty()).collect();
+    lines[lines.len().saturating_sub(3)..].join("\n")
+}
+
+impl fmt::Display for RecorderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingTool(tool) => write!(f, "{} is not installed", tool),
+            Self::Spawn { program, source } => write!(f, "Failed to start {}: {}", program, source),
+            Self::AudioModule { module, stderr } => write!(f, "Failed to load {}: {}", module, tail(stderr)),
+            Self::Concat { stderr } => write!(f, "FFmpeg concat failed: {}", tail(stderr)),
+            Self::Ffmpeg { step, stderr } => write!(f, "FFmpeg {} failed: {}", step, tail(stderr)),
+            Self::NoSession => write!(f, "No recording in progress"),
+            Self::NotPaused => write!(f, "The recording is not paused"),
+            Self::MissingDevice(device) => write!(f, "No {} is selected for recording both mic and system audio", device),
+            Self::LowDiskSpace { path, available, required } => write!(
+                f,
+                "Only {} free in {}, recording needs at least {}",
+                disk::format_size(*available),
+                path.display(),
+                disk::format_size(*required)
+            ),
+            Self::NoSegments => write!(f, "No recordings made"),
+            Self::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for RecorderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Spawn { source, .. } => Some(source),
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RecorderError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
@@ -0,0 +1,382 @@
+use std::fmt;
+use std::str::FromStr;
+
+use display_info::DisplayInfo;
+
+/// A capture region in compositor coordinates, as produced by slurp and
+/// consumed by `wl-screenrec -g`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Geometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Shape a selected region is adjusted to before recording
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizeConstraint {
+    Free,
+    Aspect(u32, u32), // "16:9"
+    Fixed(u32, u32),  // "1280x720"
+}
+
+impl FromStr for SizeConstraint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let pair = |sep: char| -> Option<(u32, u32)> {
+            let (a, b) = s.split_once(sep)?;
+            let (a, b) = (a.trim().parse().ok()?, b.trim().parse().ok()?);
+            (a > 0 && b > 0).then_some((a, b))
+        };
+
+        if s.is_empty() || s.eq_ignore_ascii_case("free") {
+            Ok(Self::Free)
+        } else if let Some((w, h)) = pair(':') {
+            Ok(Self::Aspect(w, h))
+        } else if let Some((w, h)) = pair('x') {
+            Ok(Self::Fixed(w, h))
+        } else {
+            Err(format!("Invalid size constraint '{}'", s))
+        }
+    }
+}
+
+impl fmt::Display for SizeConstraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Free => write!(f, "Free"),
+            Self::Aspect(w, h) => write!(f, "{}:{}", w, h),
+            Self::Fixed(w, h) => write!(f, "{}x{}", w, h),
+        }
+    }
+}
+
+impl FromStr for Geometry {
+    type Err = String;
+
+    // Parse slurp's "X,Y WxH" format
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid region '{}', expected \"X,Y WxH\"", s.trim());
+
+        let (pos, size) = s.trim().split_once(' ').ok_or_else(invalid)?;
+        let (x, y) = pos.split_once(',').ok_or_else(invalid)?;
+        let (w, h) = size.trim().split_once('x').ok_or_else(invalid)?;
+
+        let x: i32 = x.trim().parse().map_err(|_| invalid())?;
+        let y: i32 = y.trim().parse().map_err(|_| invalid())?;
+        let w: i64 = w.trim().parse().map_err(|_| invalid())?;
+        let h: i64 = h.trim().parse().map_err(|_| invalid())?;
+
+        if w <= 0 || h <= 0 {
+            return Err(format!("Region size {}x{} must be positive", w, h));
+        }
+
+        Ok(Self {
+            x,
+            y,
+            width: u32::try_from(w).map_err(|_| invalid())?,
+            height: u32::try_from(h).map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl fmt::Display for Geometry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{} {}x{}", self.x, self.y, self.width, self.height)
+    }
+}
+
+// Written in the same "X,Y WxH" form slurp uses
+impl serde::Serialize for Geometry {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl Geometry {
+    fn right(&self) -> i64 {
+        self.x as i64 + self.width as i64
+    }
+
+    fn bottom(&self) -> i64 {
+        self.y as i64 + self.height as i64
+    }
+
+    // Overlapping part of this region and an output, if any
+    fn intersect(&self, output: &Geometry) -> Option<Self> {
+        let left = (self.x as i64).max(output.x as i64);
+        let top = (self.y as i64).max(output.y as i64);
+        let right = self.right().min(output.right());
+        let bottom = self.bottom().min(output.bottom());
+
+        if right <= left || bottom <= top {
+            return None;
+        }
+
+        Some(Self {
+            x: left as i32,
+            y: top as i32,
+            width: (right - left) as u32,
+            height: (bottom - top) as u32,
+        })
+    }
+
+    fn center(&self) -> (i64, i64) {
+        (self.x as i64 + self.width as i64 / 2, self.y as i64 + self.height as i64 / 2)
+    }
+
+    fn centered_at(center: (i64, i64), width: u32, height: u32) -> Self {
+        Self {
+            x: (center.0 - width as i64 / 2) as i32,
+            y: (center.1 - height as i64 / 2) as i32,
+            width,
+            height,
+        }
+    }
+
+    /// Reshape the region around its center. Aspect ratios shrink the longer
+    /// side so the result stays inside the selection; fixed sizes replace it.
+    pub fn constrain(self, constraint: SizeConstraint) -> Self {
+        match constraint {
+            SizeConstraint::Free => self,
+            SizeConstraint::Aspect(rw, rh) => {
+                let (w, h) = (self.width as u64, self.height as u64);
+                let (width, height) = if w * rh as u64 > h * rw as u64 {
+                    (h * rw as u64 / rh as u64, h)
+                } else {
+                    (w, w * rh as u64 / rw as u64)
+                };
+                Self::centered_at(self.center(), width.max(1) as u32, height.max(1) as u32)
+            }
+            SizeConstraint::Fixed(width, height) => Self::centered_at(self.center(), width, height),
+        }
+    }
+
+    /// Slide the region onto the output it overlaps the most without resizing it,
+    /// so a fixed size near a screen edge keeps its dimensions where possible.
+    pub fn shift_into(self, outputs: &[Geometry]) -> Self {
+        let best = outputs
+            .iter()
+            .filter_map(|o| self.intersect(o).map(|g| (o, g.width as u64 * g.height as u64)))
+            .max_by_key(|(_, area)| *area)
+            .map(|(o, _)| o);
+
+        let Some(output) = best else { return self };
+        let shift = |pos: i32, len: u32, start: i32, size: u32| -> i32 {
+            let max = start as i64 + size as i64 - len as i64;
+            (pos as i64).min(max).max(start as i64) as i32
+        };
+
+        Self {
+            x: shift(self.x, self.width, output.x, output.width),
+            y: shift(self.y, self.height, output.y, output.height),
+            ..self
+        }
+    }
+
+    /// Round the size down to even numbers, which yuv420 encoders require
+    pub fn even(self) -> Result<Self, String> {
+        let width = self.width & !1;
+        let height = self.height & !1;
+        if width == 0 || height == 0 {
+            return Err(format!("Region {}x{} is too small to record", self.width, self.height));
+        }
+        Ok(Self { width, height, ..self })
+    }
+
+    /// Clamp the region to the output it overlaps the most. Regions spanning
+    /// several outputs can't be captured by wl-screenrec, so the rest is cut off.
+    pub fn clamp_to(self, outputs: &[Geometry]) -> Result<Self, String> {
+        if outputs.is_empty() {
+            return Ok(self);
+        }
+
+        outputs
+            .iter()
+            .filter_map(|o| self.intersect(o))
+            .max_by_key(|g| g.width as u64 * g.height as u64)
+            .ok_or(format!("Region {} is outside the current outputs", self))
+    }
+
+    /// Check that the region lies entirely on one of the given outputs
+    pub fn validate_on(&self, outputs: &[Geometry]) -> Result<(), String> {
+        let fits = outputs.iter().any(|o| self.intersect(o) == Some(*self));
+        if fits {
+            Ok(())
+        } else {
+            Err(format!("Region {} is outside the current outputs", self))
+        }
+    }
+
+    /// Parse, clamp and round a region in one go, ready to hand to wl-screenrec
+    pub fn sanitize(s: &str, outputs: &[Geometry]) -> Result<Self, String> {
+        Self::sanitize_with(s, SizeConstraint::Free, outputs)
+    }
+
+    /// The largest even-sized region with exactly the given aspect ratio that
+    /// fits inside this one, centered on it
+    pub fn fit_aspect(self, rw: u32, rh: u32) -> Result<Self, String> {
+        let divisor = gcd(rw, rh);
+        let (rw, rh) = ((rw / divisor) as u64, (rh / divisor) as u64);
+        // A reduced ratio has at least one odd side, so only even multiples
+        // give even sizes
+        let n = (self.width as u64 / rw).min(self.height as u64 / rh) & !1;
+        if n == 0 {
+            return Err(format!("Region {}x{} is too small for a {}:{} aspect ratio", self.width, self.height, rw, rh));
+        }
+        Ok(Self::centered_at(self.center(), (n * rw) as u32, (n * rh) as u32))
+    }
+
+    /// Like `sanitize`, reshaping the region to the constraint first. A fixed
+    /// size that doesn't fit on the output is an error rather than cut down,
+    /// and an aspect ratio is fitted again after clamping so it stays exact.
+    pub fn sanitize_with(s: &str, constraint: SizeConstraint, outputs: &[Geometry]) -> Result<Self, String> {
+        let region = s.parse::<Self>()?
+            .constrain(constraint)
+            .shift_into(outputs)
+            .clamp_to(outputs)?;
+
+        match constraint {
+            SizeConstraint::Free => region.even(),
+            SizeConstraint::Aspect(rw, rh) => region.fit_aspect(rw, rh),
+            SizeConstraint::Fixed(width, height) => {
+                if region.width < width || region.height < height {
+                    return Err(format!("A fixed size of {}x{} doesn't fit on the output", width, height));
+                }
+                region.even()
+            }
+        }
+    }
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// Where each output sits in compositor coordinates
+pub fn outputs() -> Result<Vec<Geometry>, String> {
+    let displays = DisplayInfo::all().map_err(|e| format!("Failed to query outputs: {}", e))?;
+    Ok(displays
+        .iter()
+        .map(|d| Geometry { x: d.x, y: d.y, width: d.width, height: d.height })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn geometry(x: i32, y: i32, width: u32, height: u32) -> Geometry {
+        Geometry { x, y, width, height }
+    }
+
+    // A 1920x1080 output with a 2560x1440 one to its left
+    fn two_outputs() -> Vec<Geometry> {
+        vec![geometry(0, 0, 1920, 1080), geometry(-2560, 0, 2560, 1440)]
+    }
+
+    #[test]
+    fn parses_and_displays_slurp_format() {
+        for s in ["10,20 300x200", "-2560,0 2560x1440", "0,-5 1x1"] {
+            assert_eq!(s.parse::<Geometry>().unwrap().to_string(), s);
+        }
+        assert_eq!(" 1,2 3x4\n".parse::<Geometry>(), Ok(geometry(1, 2, 3, 4)));
+    }
+
+    #[test]
+    fn rejects_empty_and_negative_sizes() {
+        assert!("0,0 0x0".parse::<Geometry>().is_err());
+        assert!("0,0 100x0".parse::<Geometry>().is_err());
+        assert!("0,0 -100x100".parse::<Geometry>().is_err());
+        assert!("0,0 100".parse::<Geometry>().is_err());
+        assert!("garbage".parse::<Geometry>().is_err());
+    }
+
+    #[test]
+    fn even_rounds_down() {
+        assert_eq!(geometry(3, 5, 301, 199).even(), Ok(geometry(3, 5, 300, 198)));
+        assert_eq!(geometry(0, 0, 200, 100).even(), Ok(geometry(0, 0, 200, 100)));
+        assert!(geometry(0, 0, 1, 100).even().is_err());
+    }
+
+    #[test]
+    fn clamps_to_the_output_with_the_most_overlap() {
+        let outputs = two_outputs();
+        // Mostly on the left output
+        assert_eq!(geometry(-500, 100, 600, 400).clamp_to(&outputs), Ok(geometry(-500, 100, 500, 400)));
+        // Mostly on the right one, hanging off its bottom
+        assert_eq!(geometry(-100, 900, 600, 400).clamp_to(&outputs), Ok(geometry(0, 900, 500, 180)));
+        assert!(geometry(5000, 5000, 10, 10).clamp_to(&outputs).is_err());
+        assert_eq!(geometry(5000, 5000, 10, 10).clamp_to(&[]), Ok(geometry(5000, 5000, 10, 10)));
+    }
+
+    #[test]
+    fn shift_keeps_the_size() {
+        let outputs = two_outputs();
+        assert_eq!(geometry(1800, 1000, 400, 200).shift_into(&outputs), geometry(1520, 880, 400, 200));
+        assert_eq!(geometry(-2600, -20, 400, 200).shift_into(&outputs), geometry(-2560, 0, 400, 200));
+    }
+
+    #[test]
+    fn validates_regions_on_one_output() {
+        let outputs = two_outputs();
+        assert!(geometry(-2560, 0, 2560, 1440).validate_on(&outputs).is_ok());
+        assert!(geometry(-100, 0, 200, 200).validate_on(&outputs).is_err());
+    }
+
+    #[test]
+    fn constrains_around_the_center() {
+        let selection = geometry(0, 0, 1000, 1000);
+        assert_eq!(selection.constrain(SizeConstraint::Free), selection);
+        assert_eq!(selection.constrain(SizeConstraint::Aspect(16, 9)), geometry(0, 219, 1000, 562));
+        assert_eq!(selection.constrain(SizeConstraint::Fixed(200, 100)), geometry(400, 450, 200, 100));
+    }
+
+    #[test]
+    fn sanitize_combines_every_step() {
+        let outputs = two_outputs();
+        // Shifted onto the output first, then rounded to an even size
+        let region = Geometry::sanitize_with("1800,1000 401x201", SizeConstraint::Free, &outputs);
+        assert_eq!(region, Ok(geometry(1519, 879, 400, 200)));
+    }
+
+    #[test]
+    fn rejects_fixed_sizes_larger_than_the_output() {
+        let outputs = two_outputs();
+        let region = Geometry::sanitize_with("0,0 100x100", SizeConstraint::Fixed(2000, 720), &outputs);
+        assert!(region.is_err());
+        // Fits once shifted onto the output
+        let region = Geometry::sanitize_with("1800,1000 100x100", SizeConstraint::Fixed(1280, 720), &outputs);
+        assert_eq!(region, Ok(geometry(640, 360, 1280, 720)));
+    }
+
+    #[test]
+    fn keeps_the_aspect_ratio_after_clamping() {
+        let outputs = two_outputs();
+        // The 16:9 region is cut off by the bottom of the output, then refitted
+        let region = Geometry::sanitize_with("0,0 1920x2000", SizeConstraint::Aspect(16, 9), &outputs).unwrap();
+        assert_eq!(region.width * 9, region.height * 16);
+        assert_eq!((region.width % 2, region.height % 2), (0, 0));
+        assert_eq!(region.validate_on(&outputs), Ok(()));
+        // Rounding 1000x562 to even sizes alone would lose the ratio
+        let region = Geometry::sanitize_with("0,0 1000x1000", SizeConstraint::Aspect(16, 9), &outputs);
+        assert_eq!(region, Ok(geometry(4, 221, 992, 558)));
+        assert!(geometry(0, 0, 20, 20).fit_aspect(16, 9).is_err());
+    }
+
+    #[test]
+    fn parses_size_constraints() {
+        assert_eq!("".parse(), Ok(SizeConstraint::Free));
+        assert_eq!("free".parse(), Ok(SizeConstraint::Free));
+        assert_eq!("16:9".parse(), Ok(SizeConstraint::Aspect(16, 9)));
+        assert_eq!(" 1280 x 720 ".parse(), Ok(SizeConstraint::Fixed(1280, 720)));
+        assert!("0:9".parse::<SizeConstraint>().is_err());
+        assert!("16/9".parse::<SizeConstraint>().is_err());
+        for constraint in [SizeConstraint::Free, SizeConstraint::Aspect(4, 3), SizeConstraint::Fixed(640, 480)] {
+            assert_eq!(constraint.to_string().parse(), Ok(constraint));
+        }
+    }
+}
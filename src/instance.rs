@@ -0,0 +1,87 @@
+// One Roton per user: the first instance listens on a socket in the runtime
+// dir and later launches just ask it to show its window. Everything a
+// session creates outside its own process (pulse sinks, temp files) carries
+// a session name so instances started with --new-instance stay apart.
+
+use std::io::{self, Read, Write};
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use log::{info, warn};
+
+const ACTIVATE: &[u8] = b"activate\n";
+
+static SESSIONS: AtomicU32 = AtomicU32::new(0);
+
+pub enum Claim {
+    /// This is the only instance, activation requests arrive here
+    Primary(UnixListener),
+    /// Another instance is running and was asked to show itself
+    Forwarded,
+}
+
+fn socket_path() -> PathBuf {
+    if let Some(dir) = std::env::var_os("XDG_RUNTIME_DIR") {
+        return PathBuf::from(dir).join("roton.sock");
+    }
+    // The temp dir is shared by every user, so keep each user's socket apart
+    let uid = std::fs::metadata("/proc/self").map(|m| m.uid()).unwrap_or_default();
+    std::env::temp_dir().join(format!("roton-{}.sock", uid))
+}
+
+/// Become the running instance, or hand over to the one that already is
+pub fn claim() -> io::Result<Claim> {
+    let path = socket_path();
+    match UnixListener::bind(&path) {
+        Ok(listener) => return Ok(Claim::Primary(listener)),
+        Err(e) if e.kind() != io::ErrorKind::AddrInUse => return Err(e),
+        Err(_) => {}
+    }
+
+    match UnixStream::connect(&path) {
+        Ok(mut stream) => {
+            stream.write_all(ACTIVATE)?;
+            Ok(Claim::Forwarded)
+        }
+        Err(_) => {
+            // Left behind by an instance that crashed
+            info!("Removing stale socket {}", path.display());
+            std::fs::remove_file(&path)?;
+            UnixListener::bind(&path).map(Claim::Primary)
+        }
+    }
+}
+
+/// Call `activate` for every later launch, from a background thread
+pub fn listen(listener: UnixListener, activate: impl Fn() + Send + 'static) {
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut message = Vec::new();
+            match stream.and_then(|s| s.take(64).read_to_end(&mut message)) {
+                Ok(_) if message == ACTIVATE => {
+                    info!("Another launch asked to show the window");
+                    activate();
+                }
+                Ok(_) => warn!("Ignoring unexpected message on {}", socket_path().display()),
+                Err(e) => warn!("Error reading from {}: {}", socket_path().display(), e),
+            }
+        }
+    });
+}
+
+/// Remove the socket so the next launch starts fresh
+pub fn release() {
+    let _ = std::fs::remove_file(socket_path());
+}
+
+/// A name for one recording session, unique across running instances
+pub fn session_name() -> String {
+    format!("{}-{}", std::process::id(), SESSIONS.fetch_add(1, Ordering::Relaxed))
+}
+
+/// A file in the system temp dir that no other instance uses
+pub fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("roton-{}-{}", std::process::id(), name))
+}
@@ -0,0 +1,274 @@
+// Layered configuration: built-in defaults, then config.json, then `.env`
+// and `ROTON_*` environment variables, then command-line flags. Values from
+// the last two layers are pinned for the run and never written back.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::sync::OnceLock;
+
+use log::{info, warn};
+use serde_json::{Map, Value};
+
+use crate::config::Settings;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Default,
+    ConfigFile,
+    DotEnv,
+    Env,
+    Cli,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Default => write!(f, "default"),
+            Self::ConfigFile => write!(f, "config.json"),
+            Self::DotEnv => write!(f, ".env"),
+            Self::Env => write!(f, "environment"),
+            Self::Cli => write!(f, "command line"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Override {
+    value: Value,
+    source: Source,
+    origin: String, // "ROTON_SAVE_PATH" or "--save-path"
+}
+
+/// One setting as the app sees it after all layers are applied
+#[derive(Debug, Clone)]
+pub struct EffectiveSetting {
+    pub key: String,
+    pub value: String,
+    pub source: Source,
+    pub origin: Option<String>,
+}
+
+static OVERRIDES: OnceLock<BTreeMap<String, Override>> = OnceLock::new();
+static NEW_INSTANCE: OnceLock<bool> = OnceLock::new();
+
+fn defaults() -> Map<String, Value> {
+    match serde_json::to_value(Settings::default()) {
+        Ok(Value::Object(map)) => map,
+        _ => Map::new(),
+    }
+}
+
+fn env_name(key: &str) -> String {
+    format!("ROTON_{}", key.to_uppercase())
+}
+
+fn flag_name(key: &str) -> String {
+    format!("--{}", key.replace('_', "-"))
+}
+
+// Turn a raw string into a value of the same type as the default, rejecting
+// anything the settings file itself wouldn't accept
+fn parse_value(key: &str, raw: &str, defaults: &Map<String, Value>) -> Result<Value, String> {
+    let default = defaults.get(key).ok_or_else(|| format!("unknown setting {}", key))?;
+    let value = if default.is_string() {
+        Value::String(raw.to_string())
+    } else {
+        serde_json::from_str(raw).map_err(|_| format!("invalid value '{}' for {}", raw, key))?
+    };
+
+    let mut probe = defaults.clone();
+    probe.insert(key.to_string(), value.clone());
+    let mut settings: Settings = serde_json::from_value(Value::Object(probe))
+        .map_err(|_| format!("invalid value '{}' for {}", raw, key))?;
+
+    // Values that parse but aren't valid choices get reset by validate
+    let parsed = serde_json::to_value(&settings).ok();
+    settings.validate();
+    if serde_json::to_value(&settings).ok() != parsed {
+        return Err(format!("invalid value '{}' for {}", raw, key));
+    }
+    Ok(value)
+}
+
+/// Load `.env` into the environment without replacing variables that are
+/// already set. Returns the names it set. Runs before logging is set up so
+/// `.env` can also carry `ROTON_LOG`.
+pub fn load_dotenv() -> HashSet<String> {
+    let names = || std::env::vars_os().filter_map(|(name, _)| name.into_string().ok());
+    let before: HashSet<String> = names().collect();
+    let _ = dotenv::dotenv();
+    names().filter(|name| !before.contains(name)).collect()
+}
+
+fn print_help(defaults: &Map<String, Value>) {
+    println!("Usage: roton [--<setting> <value>]... [--print-config] [--new-instance]");
+    println!();
+    println!("Every setting can also be pinned with a ROTON_<SETTING> environment");
+    println!("variable or in .env. Pinned values are not saved to config.json.");
+    println!();
+    println!("Launching again shows the running window; --new-instance starts a");
+    println!("separate one instead.");
+    println!();
+    for key in defaults.keys() {
+        println!("  {:<28} {}", flag_name(key), env_name(key));
+    }
+}
+
+fn print_config() {
+    for setting in effective(crate::config::SettingsFile::load().active()) {
+        let origin = setting.origin.map(|o| format!(" ({})", o)).unwrap_or_default();
+        println!("{} = {}  [{}{}]", setting.key, setting.value, setting.source, origin);
+    }
+}
+
+/// Collect overrides from the environment and the command line. `dotenv` is
+/// what `load_dotenv` returned, so those can be told apart from real variables.
+pub fn init(dotenv: &HashSet<String>) {
+    let defaults = defaults();
+    let mut overrides = BTreeMap::new();
+
+    for key in defaults.keys() {
+        let name = env_name(key);
+        let Ok(raw) = std::env::var(&name) else { continue };
+        match parse_value(key, &raw, &defaults) {
+            Ok(value) => {
+                let source = if dotenv.contains(&name) { Source::DotEnv } else { Source::Env };
+                overrides.insert(key.clone(), Override { value, source, origin: name });
+            }
+            Err(e) => warn!("Ignoring {}: {}", name, e),
+        }
+    }
+
+    let mut print = false;
+    let mut new_instance = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                print_help(&defaults);
+                std::process::exit(0);
+            }
+            "--print-config" => {
+                print = true;
+                continue;
+            }
+            "--new-instance" => {
+                new_instance = true;
+                continue;
+            }
+            _ => {}
+        }
+
+        let Some(flag) = arg.strip_prefix("--") else {
+            warn!("Ignoring argument {}", arg);
+            continue;
+        };
+        let (key, raw) = match flag.split_once('=') {
+            Some((key, raw)) => (key.replace('-', "_"), Some(raw.to_string())),
+            None => (flag.replace('-', "_"), None),
+        };
+        if !defaults.contains_key(&key) {
+            warn!("Ignoring unknown option {}", arg);
+            continue;
+        }
+        let Some(raw) = raw.or_else(|| args.next()) else {
+            warn!("Missing value for {}", arg);
+            continue;
+        };
+
+        match parse_value(&key, &raw, &defaults) {
+            Ok(value) => {
+                let origin = flag_name(&key);
+                overrides.insert(key, Override { value, source: Source::Cli, origin });
+            }
+            Err(e) => warn!("Ignoring {}: {}", arg, e),
+        }
+    }
+
+    for (key, o) in &overrides {
+        info!("Pinned {} from {} ({})", key, o.source, o.origin);
+    }
+    let _ = OVERRIDES.set(overrides);
+    let _ = NEW_INSTANCE.set(new_instance);
+
+    if print {
+        print_config();
+        std::process::exit(0);
+    }
+}
+
+/// Whether --new-instance asked to run next to an already running Roton
+pub fn new_instance() -> bool {
+    NEW_INSTANCE.get().copied().unwrap_or(false)
+}
+
+// Replace the pinned keys of `settings` with values from `from`
+fn merge(settings: &Settings, from: impl Fn(&str, &Override) -> Option<Value>) -> Settings {
+    let Some(overrides) = OVERRIDES.get().filter(|o| !o.is_empty()) else {
+        return settings.clone();
+    };
+    let Ok(Value::Object(mut map)) = serde_json::to_value(settings) else {
+        return settings.clone();
+    };
+    for (key, o) in overrides {
+        if let Some(value) = from(key, o) {
+            map.insert(key.clone(), value);
+        }
+    }
+    serde_json::from_value(Value::Object(map)).unwrap_or_else(|_| settings.clone())
+}
+
+/// Settings with the environment and command-line values on top
+pub fn apply(settings: &Settings) -> Settings {
+    merge(settings, |_, o| Some(o.value.clone()))
+}
+
+/// Undo `apply` before saving: pinned keys get the value `stored` in config.json
+pub fn unapply(settings: &Settings, stored: &Settings) -> Settings {
+    let stored = serde_json::to_value(stored).unwrap_or_default();
+    merge(settings, |key, _| stored.get(key).cloned())
+}
+
+/// Names of the settings pinned for this run, with where they came from
+pub fn pinned() -> Vec<String> {
+    OVERRIDES
+        .get()
+        .map(|o| o.iter().map(|(key, o)| format!("{} ({})", key, o.origin)).collect())
+        .unwrap_or_default()
+}
+
+/// Keys set from the environment or the command line
+pub fn pinned_keys() -> Vec<String> {
+    OVERRIDES.get().map(|o| o.keys().cloned().collect()).unwrap_or_default()
+}
+
+/// Every setting with its effective value and the layer it came from. Saved
+/// values equal to the built-in default are reported as defaults.
+pub fn effective(stored: &Settings) -> Vec<EffectiveSetting> {
+    let defaults = defaults();
+    let stored = serde_json::to_value(stored).unwrap_or_default();
+    let overrides = OVERRIDES.get();
+
+    defaults
+        .iter()
+        .map(|(key, default)| {
+            let pinned = overrides.and_then(|o| o.get(key));
+            let (value, source, origin) = match pinned {
+                Some(o) => (&o.value, o.source, Some(o.origin.clone())),
+                None => match stored.get(key) {
+                    Some(value) if value != default => (value, Source::ConfigFile, None),
+                    _ => (default, Source::Default, None),
+                },
+            };
+            EffectiveSetting {
+                key: key.clone(),
+                value: match value {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                },
+                source,
+                origin,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,167 @@
+use std::ffi::OsStr;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::process::{Child, Command, Output};
+use std::sync::Mutex;
+
+use directories::ProjectDirs;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+// Rotate once the log passes this size, keeping roton.log.1 .. roton.log.3
+const MAX_LOG_SIZE: u64 = 1024 * 1024;
+const KEEP_LOGS: usize = 3;
+
+struct FileLogger {
+    file: Mutex<Option<File>>,
+    // Everything goes to the file, only this level and up to stderr
+    stderr_level: Level,
+}
+
+/// Directory holding the log files, `~/.local/state/roton` on most systems
+pub fn log_dir() -> Option<PathBuf> {
+    ProjectDirs::from("com", "ferdinankurnian", "roton")
+        .map(|dirs| dirs.state_dir().unwrap_or(dirs.data_local_dir()).to_path_buf())
+}
+
+pub fn log_path() -> Option<PathBuf> {
+    log_dir().map(|dir| dir.join("roton.log"))
+}
+
+fn open_log() -> Option<File> {
+    let path = log_path()?;
+    fs::create_dir_all(path.parent()?).ok()?;
+    OpenOptions::new().create(true).append(true).open(path).ok()
+}
+
+// Shift roton.log -> roton.log.1 -> ... dropping the oldest
+fn rotate() {
+    let Some(path) = log_path() else { return };
+    let numbered = |n: usize| path.with_extension(format!("log.{}", n));
+
+    let _ = fs::remove_file(numbered(KEEP_LOGS));
+    for n in (1..KEEP_LOGS).rev() {
+        let _ = fs::rename(numbered(n), numbered(n + 1));
+    }
+    let _ = fs::rename(&path, numbered(1));
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // Keep dependencies (slint, winit, ...) out of the log unless they complain
+        metadata.target().starts_with("roton") || metadata.level() <= Level::Warn
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        if record.level() <= self.stderr_level {
+            eprintln!("[{}] {}", record.level(), record.args());
+        }
+
+        let Ok(mut file) = self.file.lock() else { return };
+        let too_big = file.as_ref()
+            .and_then(|f| f.metadata().ok())
+            .is_some_and(|m| m.len() > MAX_LOG_SIZE);
+        if too_big {
+            rotate();
+            *file = open_log();
+        }
+
+        if let Some(file) = file.as_mut() {
+            let _ = writeln!(
+                file,
+                "{} [{}] {}",
+                chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+                record.level(),
+                record.args()
+            );
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            if let Some(file) = file.as_mut() {
+                let _ = file.flush();
+            }
+        }
+    }
+}
+
+/// Install the logger. `ROTON_LOG` (error, warn, info, debug) sets how much
+/// is echoed to the terminal; the file always gets debug output.
+pub fn init() {
+    let stderr_level = std::env::var("ROTON_LOG")
+        .ok()
+        .and_then(|l| l.parse().ok())
+        .unwrap_or(Level::Info);
+
+    let logger = FileLogger {
+        file: Mutex::new(open_log()),
+        stderr_level,
+    };
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(LevelFilter::Debug);
+    }
+    log::info!("Roton {} started", env!("CARGO_PKG_VERSION"));
+}
+
+fn quote(arg: &OsStr) -> String {
+    let arg = arg.to_string_lossy();
+    if !arg.is_empty() && arg.chars().all(|c| c.is_ascii_alphanumeric() || "-_./:=,%".contains(c)) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// Log the full command line about to be run, quoted so it can be pasted into a shell
+pub fn command(cmd: &Command) {
+    let line = std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(quote)
+        .collect::<Vec<_>>()
+        .join(" ");
+    log::debug!("Running: {}", line);
+}
+
+/// Log what a finished command wrote to stderr; failures are logged as warnings
+pub fn output(program: &str, output: &Output) {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stderr = stderr.trim();
+    if !output.status.success() {
+        log::warn!("{} exited with {}: {}", program, output.status, stderr);
+    } else if !stderr.is_empty() {
+        log::debug!("{} stderr: {}", program, stderr);
+    }
+}
+
+/// Log what a long-running child spawned with a piped stderr writes there,
+/// line by line from a background thread, so it is rotated with the rest
+pub fn follow_stderr(program: &str, child: &mut Child) {
+    let Some(stderr) = child.stderr.take() else { return };
+    let program = program.to_string();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stderr).split(b'\n').map_while(Result::ok) {
+            let line = String::from_utf8_lossy(&line);
+            if !line.trim().is_empty() {
+                log::debug!("{}: {}", program, line.trim_end());
+            }
+        }
+    });
+}
+
+/// The last `max_bytes` of the current log
+pub fn tail(max_bytes: u64) -> String {
+    let Some(mut file) = log_path().and_then(|p| File::open(p).ok()) else {
+        return String::new();
+    };
+    let len = file.metadata().map(|m| m.len()).unwrap_or(0);
+    let _ = file.seek(SeekFrom::Start(len.saturating_sub(max_bytes)));
+
+    let mut bytes = Vec::new();
+    let _ = file.read_to_end(&mut bytes);
+    String::from_utf8_lossy(&bytes).to_string()
+}
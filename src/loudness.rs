@@ -0,0 +1,104 @@
+use std::process::{Command, Stdio};
+
+use log::info;
+
+use crate::logging;
+
+// EBU R128 defaults used alongside the configurable integrated target
+const TRUE_PEAK: f32 = -1.5;
+const LOUDNESS_RANGE: f32 = 11.0;
+
+#[derive(Debug, Clone, Copy)]
+pub struct LoudnessStats {
+    pub integrated: f32, // LUFS
+    pub true_peak: f32,  // dBTP
+}
+
+// Values reported by ffmpeg's loudnorm filter with print_format=json
+struct Measurement {
+    input_i: String,
+    input_tp: String,
+    input_lra: String,
+    input_thresh: String,
+    target_offset: String,
+    output_i: f32,
+    output_tp: f32,
+}
+
+fn parse_measurement(stderr: &str) -> Result<Measurement, String> {
+    // The JSON block is the last thing loudnorm prints
+    let start = stderr.rfind('{').ok_or("No loudnorm output found")?;
+    let end = stderr.rfind('}').ok_or("No loudnorm output found")?;
+    let json: serde_json::Value = serde_json::from_str(&stderr[start..=end])
+        .map_err(|e| format!("Invalid loudnorm output: {}", e))?;
+
+    let field = |key: &str| -> Result<String, String> {
+        json.get(key)
+            .and_then(|v| v.as_str())
+            .map(|v| v.to_string())
+            .ok_or(format!("Missing '{}' in loudnorm output", key))
+    };
+    let number = |key: &str| -> Result<f32, String> {
+        field(key)?.parse::<f32>().map_err(|e| format!("Bad '{}' in loudnorm output: {}", key, e))
+    };
+
+    Ok(Measurement {
+        input_i: field("input_i")?,
+        input_tp: field("input_tp")?,
+        input_lra: field("input_lra")?,
+        input_thresh: field("input_thresh")?,
+        target_offset: field("target_offset")?,
+        output_i: number("output_i")?,
+        output_tp: number("output_tp")?,
+    })
+}
+
+fn run_ffmpeg(args: &[&str]) -> Result<String, String> {
+    let mut cmd = Command::new("ffmpeg");
+    cmd.args(args).stdin(Stdio::null());
+    logging::command(&cmd);
+    let output = cmd.output().map_err(|e| e.to_string())?;
+    logging::output("ffmpeg", &output);
+
+    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+    if !output.status.success() {
+        return Err("FFmpeg loudnorm failed".to_string());
+    }
+    Ok(stderr)
+}
+
+/// Two-pass EBU R128 normalization of the first audio track. The first pass
+/// measures the input, the second applies a linear gain using those values.
+/// Returns the loudness of the normalized output.
+pub fn normalize(input: &str, output: &str, target: f32) -> Result<LoudnessStats, String> {
+    info!("Measuring loudness of {}", input);
+    let analysis = format!(
+        "loudnorm=I={}:TP={}:LRA={}:print_format=json",
+        target, TRUE_PEAK, LOUDNESS_RANGE
+    );
+    let first = parse_measurement(&run_ffmpeg(&[
+        "-i", input, "-map", "0:a:0", "-af", &analysis, "-f", "null", "-",
+    ])?)?;
+
+    info!("Normalizing loudness to {} LUFS", target);
+    let filter = format!(
+        "loudnorm=I={}:TP={}:LRA={}:measured_I={}:measured_TP={}:measured_LRA={}:measured_thresh={}:offset={}:linear=true:print_format=json",
+        target, TRUE_PEAK, LOUDNESS_RANGE,
+        first.input_i, first.input_tp, first.input_lra, first.input_thresh, first.target_offset
+    );
+    let second = parse_measurement(&run_ffmpeg(&[
+        "-i", input,
+        "-map", "0",
+        "-c", "copy",
+        "-filter:a:0", &filter,
+        "-c:a:0", "aac",
+        // loudnorm upsamples to 192kHz internally
+        "-ar:a:0", "48000",
+        "-y", output,
+    ])?)?;
+
+    Ok(LoudnessStats {
+        integrated: second.output_i,
+        true_peak: second.output_tp,
+    })
+}
@@ -5,17 +5,343 @@ use std::sync::{Arc, Mutex};
 mod recorder;
 mod config;
 mod audio;
+mod clipboard;
+mod meter;
+mod loudness;
+mod overlay;
+mod watermark;
+mod geometry;
+mod compositor;
+mod diagnostics;
+mod error;
+mod logging;
+mod layers;
+mod probe;
+mod chapters;
+mod metadata;
+mod disk;
+mod output;
+mod instance;
+mod compose;
 
-use recorder::Recorder;
-use config::Settings;
+use recorder::{CaptureSource, Recorder, RecordingConfig};
+use config::{RegionPreset, Settings, SettingsFile};
 use audio::AudioDevice;
+use meter::LevelMeter;
+use overlay::OverlayConfig;
+use watermark::{WatermarkConfig, WatermarkContent};
+use geometry::{Geometry, SizeConstraint};
+use compositor::{Compositor, WindowInfo};
+use diagnostics::Report;
+use chapters::Chapter;
+use disk::SpaceLevel;
+use error::RecorderError;
 use slint::Model;
+use log::{error, info, warn};
 
 slint::include_modules!();
 
+// Choices like overlay corners are stored as slugs but shown capitalized in the UI
+fn slug_to_label(slug: &str) -> String {
+    slug.split('-')
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map(|c| c.to_uppercase().chain(chars).collect::<String>()).unwrap_or_default()
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn label_to_slug(label: &str) -> String {
+    label.to_lowercase().replace(' ', "-")
+}
+
+// Switch to the error page; its back button returns to `return_page`
+fn show_error(app: &AppWindow, error: &RecorderError, return_page: i32) {
+    app.set_error_message(error.to_string().into());
+    app.set_error_hint(error.hint().into());
+    app.set_error_return_page(return_page);
+    app.set_active_page(6);
+}
+
+// Enable features based on the available tools and fill the diagnostics page
+fn apply_diagnostics(app: &AppWindow, report: &Report) {
+    app.set_has_recorder(report.has("wl-screenrec"));
+    app.set_has_slurp(report.has("slurp"));
+    app.set_has_ffmpeg(report.has("ffmpeg"));
+    app.set_has_pactl(report.has("pactl"));
+    app.set_has_audio(report.audio_server.is_ok());
+    app.set_has_wl_copy(report.has("wl-copy"));
+    app.set_has_window_list(match compositor::detect() {
+        Some(Compositor::Sway) => report.has("swaymsg"),
+        Some(Compositor::Hyprland) => report.has("hyprctl"),
+        None => false,
+    });
+
+    let mut entries: Vec<DiagnosticEntry> = report
+        .tools
+        .iter()
+        .map(|tool| match &tool.path {
+            Some(path) => DiagnosticEntry {
+                name: tool.name.into(),
+                ok: true,
+                detail: tool.version.clone().unwrap_or_else(|| path.to_string_lossy().to_string()).into(),
+                impact: tool.features.into(),
+            },
+            None => DiagnosticEntry {
+                name: tool.name.into(),
+                ok: false,
+                detail: "Not found on PATH".into(),
+                impact: format!("{}: {}", if tool.required { "Required for" } else { "Disabled" }, tool.features).into(),
+            },
+        })
+        .collect();
+
+    entries.push(match &report.audio_server {
+        Ok(name) => DiagnosticEntry {
+            name: "Audio server".into(),
+            ok: true,
+            detail: name.clone().into(),
+            impact: "Recording audio".into(),
+        },
+        Err(e) => DiagnosticEntry {
+            name: "Audio server".into(),
+            ok: false,
+            detail: e.clone().into(),
+            impact: "Disabled: Recording audio".into(),
+        },
+    });
+
+    app.set_diagnostics(std::rc::Rc::new(slint::VecModel::from(entries)).into());
+}
+
+// Validate a region from slurp or the compositor and make it the recording target.
+// On failure the error is left for the screen page to show.
+fn apply_region(app: &AppWindow, raw: &str, mode: &str, constraint: SizeConstraint) -> bool {
+    let outputs = geometry::outputs().unwrap_or_else(|e| {
+        error!("Error clamping region: {}", e);
+        Vec::new()
+    });
+
+    match Geometry::sanitize_with(raw, constraint, &outputs) {
+        Ok(geo) => {
+            app.set_geometry_error("".into());
+            app.set_recording_mode(mode.into());
+            app.set_recording_geometry(geo.to_string().into());
+            true
+        }
+        Err(e) => {
+            error!("Error selecting area: {}", e);
+            app.set_geometry_error(e.into());
+            false
+        }
+    }
+}
+
+// Region pickers go back to the paused recording when changing its source
+fn area_return_page(app: &AppWindow) -> i32 {
+    if app.get_is_paused() { 3 } else { 0 }
+}
+
+// What to capture, from the region and audio choices in the UI. The region is
+// re-checked in case outputs changed since it was selected.
+fn capture_source(app: &AppWindow, geometry: &str, devices: &[AudioDevice]) -> Result<CaptureSource, String> {
+    let geometry = if geometry.is_empty() {
+        None
+    } else {
+        let outputs = geometry::outputs().unwrap_or_default();
+        Some(Geometry::sanitize(geometry, &outputs)?)
+    };
+
+    // Resolve to internal names
+    let selected_mic = app.get_selected_mic().to_string();
+    let selected_monitor = app.get_selected_monitor().to_string();
+    let output = app.get_recording_output().to_string();
+
+    Ok(CaptureSource {
+        geometry,
+        output: (!output.is_empty()).then_some(output),
+        audio_mode: available_audio_mode(app),
+        mic_device: devices.iter().find(|d| d.description == selected_mic).map(|d| d.name.clone()),
+        monitor_device: devices.iter().find(|d| d.description == selected_monitor).map(|d| d.name.clone()),
+    })
+}
+
+// The selected audio mode, or "Mute" when what it needs is missing. A mode
+// saved before a tool went away can't be picked again in the settings.
+fn available_audio_mode(app: &AppWindow) -> String {
+    let mode = app.get_audio_mode().to_string();
+    let available = match mode.as_str() {
+        "Mute" => true,
+        "Both" => app.get_has_audio() && app.get_has_pactl(),
+        _ => app.get_has_audio(),
+    };
+    if available {
+        mode
+    } else {
+        warn!("Audio mode {} is unavailable, recording without audio", mode);
+        "Mute".to_string()
+    }
+}
+
+// Grouped like the settings page, where one control can cover several keys
+fn pinned_settings() -> PinnedSettings {
+    let keys = layers::pinned_keys();
+    let is = |key: &str| keys.iter().any(|k| k == key);
+    let any = |prefix: &str| keys.iter().any(|k| k.starts_with(prefix));
+    PinnedSettings {
+        save_path: is("save_path"),
+        work_dir: is("work_dir"),
+        audio_mode: is("audio_mode"),
+        auto_copy: is("auto_copy"),
+        metadata_sidecar: is("metadata_sidecar"),
+        separate_tracks: is("separate_tracks"),
+        noise_suppression: is("noise_suppression"),
+        loudnorm: is("loudnorm") || is("loudness_target"),
+        overlay: any("overlay_"),
+        watermark: any("watermark_"),
+        mix: is("mic_volume") || is("monitor_volume"),
+    }
+}
+
+fn apply_chapters(app: &AppWindow, chapters: &[Chapter]) {
+    let entries: Vec<ChapterEntry> = chapters
+        .iter()
+        .map(|c| ChapterEntry {
+            time: chapters::format_timestamp(c.start).into(),
+            title: c.title.clone().into(),
+        })
+        .collect();
+    app.set_chapters(std::rc::Rc::new(slint::VecModel::from(entries)).into());
+    app.set_chapters_status("".into());
+}
+
+fn region_constraint(app: &AppWindow) -> SizeConstraint {
+    app.get_region_constraint().parse().unwrap_or(SizeConstraint::Free)
+}
+
+// Build the watermark from the UI state, skipping it when there is nothing to draw
+fn watermark_config(app: &AppWindow) -> Option<WatermarkConfig> {
+    if !app.get_watermark_enabled() {
+        return None;
+    }
+
+    let content = if label_to_slug(&app.get_watermark_kind()) == "image" {
+        let image = app.get_watermark_image().to_string();
+        if image.is_empty() {
+            return None;
+        }
+        WatermarkContent::Image(image)
+    } else {
+        let text = app.get_watermark_text().to_string();
+        if text.trim().is_empty() {
+            return None;
+        }
+        WatermarkContent::Text {
+            text,
+            font: app.get_watermark_font().to_string(),
+            size: app.get_watermark_font_size() as u32,
+        }
+    };
+
+    Some(WatermarkConfig {
+        content,
+        position: label_to_slug(&app.get_watermark_position()),
+        opacity: app.get_watermark_opacity() as u32,
+    })
+}
+
+fn overlay_sources(current: &str) -> slint::ModelRc<slint::SharedString> {
+    let mut sources: Vec<slint::SharedString> = overlay::list_video_devices()
+        .into_iter()
+        .map(slint::SharedString::from)
+        .collect();
+    if !current.is_empty() && !sources.iter().any(|s| s == current) {
+        sources.push(current.into());
+    }
+    std::rc::Rc::new(slint::VecModel::from(sources)).into()
+}
+
+// Show the profile list and which one is active
+fn apply_profiles(app: &AppWindow, profiles: &SettingsFile) {
+    let names: Vec<slint::SharedString> = profiles.names().into_iter().map(Into::into).collect();
+    app.set_profiles(std::rc::Rc::new(slint::VecModel::from(names)).into());
+    app.set_active_profile(profiles.active_profile.clone().into());
+}
+
+// Load a profile's settings into the UI and the recorder
+fn apply_settings(app: &AppWindow, settings: &Settings, recorder: &Mutex<Recorder>) {
+    app.set_save_path(settings.save_path.clone().into());
+    app.set_audio_mode(settings.audio_mode.clone().into());
+    app.set_work_dir(settings.work_dir.clone().into());
+    app.set_auto_copy(settings.auto_copy);
+    app.set_metadata_sidecar(settings.metadata_sidecar);
+    app.set_separate_tracks(settings.separate_tracks);
+    app.set_noise_suppression(settings.noise_suppression);
+    app.set_loudnorm(settings.loudnorm);
+    app.set_loudness_target(settings.loudness_target.round() as i32);
+    app.set_overlay_enabled(settings.overlay_enabled);
+    app.set_overlay_sources(overlay_sources(&settings.overlay_source));
+    app.set_overlay_source(settings.overlay_source.clone().into());
+    app.set_overlay_corner(slug_to_label(&settings.overlay_corner).into());
+    app.set_overlay_size(settings.overlay_size as i32);
+    app.set_watermark_enabled(settings.watermark_enabled);
+    app.set_watermark_kind(slug_to_label(&settings.watermark_kind).into());
+    app.set_watermark_text(settings.watermark_text.clone().into());
+    app.set_watermark_image(settings.watermark_image.clone().into());
+    app.set_watermark_font(settings.watermark_font.clone().into());
+    app.set_watermark_font_size(settings.watermark_font_size as i32);
+    app.set_watermark_position(slug_to_label(&settings.watermark_position).into());
+    app.set_watermark_opacity(settings.watermark_opacity as i32);
+    app.set_mic_volume(settings.mic_volume as f32);
+    app.set_monitor_volume(settings.monitor_volume as f32);
+    if let Ok(mut rec) = recorder.lock() {
+        rec.set_mic_volume(settings.mic_volume);
+        rec.set_monitor_volume(settings.monitor_volume);
+    }
+    app.set_region_constraint(settings.region_constraint.clone().into());
+    app.set_recording_mode(settings.recording_mode.clone().into());
+    app.set_recording_geometry(settings.recording_geometry.clone().into());
+    app.set_recording_output(settings.recording_output.clone().into());
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
+    let dotenv = layers::load_dotenv();
+    logging::init();
+    layers::init(&dotenv);
+
+    let listener = if layers::new_instance() {
+        None
+    } else {
+        match instance::claim() {
+            Ok(instance::Claim::Primary(listener)) => Some(listener),
+            Ok(instance::Claim::Forwarded) => {
+                info!("Roton is already running, showing its window");
+                return Ok(());
+            }
+            Err(e) => {
+                warn!("Could not check for a running instance: {}", e);
+                None
+            }
+        }
+    };
 
     let app = AppWindow::new()?;
+
+    let is_primary = listener.is_some();
+    if let Some(listener) = listener {
+        let app_weak = app.as_weak();
+        instance::listen(listener, move || {
+            let app_weak = app_weak.clone();
+            let _ = slint::invoke_from_event_loop(move || {
+                if let Some(app) = app_weak.upgrade() {
+                    if let Err(e) = app.show() {
+                        error!("Error showing window: {}", e);
+                    }
+                }
+            });
+        });
+    }
     let last_path = Arc::new(Mutex::new(None));
     
     // Store audio devices to map friendly names back to internal names
@@ -33,15 +359,160 @@ fn main() -> Result<(), Box<dyn Error>> {
     let recorder = Arc::new(Mutex::new(Recorder::new()));
 
     // Load persisted settings
-    let settings = Settings::load();
-    app.set_save_path(settings.save_path.into());
-    app.set_audio_mode(settings.audio_mode.into());
+    let profiles = SettingsFile::load();
+    apply_profiles(&app, &profiles);
+    apply_settings(&app, &profiles.effective(), &recorder);
+    app.set_pinned_settings(layers::pinned().join(", ").into());
+    app.set_pinned(pinned_settings());
+
+    // Profiles: every change is saved right away, then the UI is reloaded from the file
+    let reload_profile = {
+        let app_weak = app.as_weak();
+        let recorder = recorder.clone();
+        move |profiles: &SettingsFile| {
+            if let Some(app) = app_weak.upgrade() {
+                if let Err(e) = profiles.save() {
+                    error!("Error saving settings: {}", e);
+                }
+                apply_profiles(&app, profiles);
+                apply_settings(&app, &profiles.effective(), &recorder);
+                app.set_profile_status("".into());
+            }
+        }
+    };
+
+    app.on_switch_profile({
+        let reload_profile = reload_profile.clone();
+        move |name| {
+            let mut profiles = SettingsFile::load();
+            if profiles.switch(&name) {
+                info!("Switched to profile {}", name);
+                reload_profile(&profiles);
+            }
+        }
+    });
+
+    app.on_new_profile({
+        let reload_profile = reload_profile.clone();
+        move |name| {
+            let mut profiles = SettingsFile::load();
+            let name = profiles.create(&name);
+            info!("Created profile {}", name);
+            reload_profile(&profiles);
+        }
+    });
+
+    app.on_delete_profile({
+        let reload_profile = reload_profile.clone();
+        move || {
+            let mut profiles = SettingsFile::load();
+            let name = profiles.active_profile.clone();
+            if profiles.delete_active() {
+                info!("Deleted profile {}", name);
+                reload_profile(&profiles);
+            }
+        }
+    });
+
+    app.on_import_profile({
+        let app_weak = app.as_weak();
+        let reload_profile = reload_profile.clone();
+        move || {
+            let Some(file) = rfd::FileDialog::new()
+                .set_title("Import Profile")
+                .add_filter("JSON", &["json"])
+                .pick_file() else { return };
+
+            let mut profiles = SettingsFile::load();
+            match profiles.import(&file) {
+                Ok(names) => {
+                    info!("Imported profiles {:?} from {}", names, file.display());
+                    reload_profile(&profiles);
+                }
+                Err(e) => {
+                    error!("Error importing profile: {}", e);
+                    if let Some(app) = app_weak.upgrade() {
+                        app.set_profile_status(e.into());
+                    }
+                }
+            }
+        }
+    });
+
+    app.on_export_profile({
+        let app_weak = app.as_weak();
+        move || {
+            let profiles = SettingsFile::load();
+            let Some(file) = rfd::FileDialog::new()
+                .set_title("Export Profile")
+                .add_filter("JSON", &["json"])
+                .set_file_name(format!("roton-{}.json", label_to_slug(&profiles.active_profile)))
+                .save_file() else { return };
+
+            let status = match profiles.export_active(&file) {
+                Ok(()) => format!("Exported to {}", file.display()),
+                Err(e) => {
+                    error!("Error exporting profile: {}", e);
+                    e
+                }
+            };
+            if let Some(app) = app_weak.upgrade() {
+                app.set_profile_status(status.into());
+            }
+        }
+    });
 
     // Check dependencies
-    let has_slurp = Recorder::is_installed("slurp");
-    let has_ffmpeg = Recorder::is_installed("ffmpeg");
-    app.set_has_slurp(has_slurp);
-    app.set_has_ffmpeg(has_ffmpeg);
+    let report = diagnostics::run();
+    apply_diagnostics(&app, &report);
+    app.on_run_diagnostics({
+        let app_weak = app.as_weak();
+        move || {
+            if let Some(app) = app_weak.upgrade() {
+                apply_diagnostics(&app, &diagnostics::run());
+            }
+        }
+    });
+
+    app.on_copy_diagnostics({
+        let app_weak = app.as_weak();
+        move || {
+            let Some(app) = app_weak.upgrade() else { return };
+            let report = diagnostics::run();
+            apply_diagnostics(&app, &report);
+            let bundle = diagnostics::bundle(&report);
+
+            // Keep a copy on disk so it can be attached to an issue later
+            let file = logging::log_dir().map(|dir| {
+                dir.join(format!("roton-report-{}.txt", chrono::Local::now().format("%Y-%m-%d_%H-%M-%S")))
+            });
+            let saved = file.filter(|path| match std::fs::write(path, &bundle) {
+                Ok(()) => true,
+                Err(e) => {
+                    error!("Error saving diagnostics report: {}", e);
+                    false
+                }
+            });
+
+            let copied = app.get_has_wl_copy() && match clipboard::copy_text(&bundle) {
+                Ok(()) => true,
+                Err(e) => {
+                    error!("Error copying diagnostics report: {}", e);
+                    false
+                }
+            };
+
+            let status = match (copied, saved) {
+                (true, Some(path)) => format!("Report copied to clipboard and saved to {}", path.display()),
+                (true, None) => "Report copied to clipboard".to_string(),
+                (false, Some(path)) => format!("Report saved to {}", path.display()),
+                (false, None) => "Could not copy or save the report".to_string(),
+            };
+            info!("{}", status);
+            app.set_diagnostics_status(status.into());
+        }
+    });
+
     
     // Refresh audio devices logic
     let refresh_audio = {
@@ -89,6 +560,74 @@ fn main() -> Result<(), Box<dyn Error>> {
     
     app.on_refresh_devices(refresh_audio.clone());
 
+    // Live level meters, only running while the settings or recording page is visible
+    let meters = Arc::new(Mutex::new(Vec::<LevelMeter>::new()));
+
+    app.on_update_meters({
+        let app_weak = app.as_weak();
+        let audio_devices = audio_devices.clone();
+        let meters = meters.clone();
+        move || {
+            let Some(app) = app_weak.upgrade() else { return };
+
+            // Stop whatever is running before deciding what to start
+            if let Ok(mut running) = meters.lock() {
+                running.clear();
+            }
+            app.set_mic_level(Default::default());
+            app.set_monitor_level(Default::default());
+
+            let page = app.get_active_page();
+            let audio_mode = app.get_audio_mode().to_string();
+            if (page != 1 && page != 3) || audio_mode == "Mute" {
+                return;
+            }
+
+            let selected_mic = app.get_selected_mic().to_string();
+            let selected_monitor = app.get_selected_monitor().to_string();
+            let mut sources = Vec::new();
+
+            if let Ok(devices) = audio_devices.lock() {
+                if page == 1 || audio_mode == "Mic" || audio_mode == "Both" {
+                    if let Some(dev) = devices.iter().find(|d| d.description == selected_mic) {
+                        sources.push((dev.name.clone(), true));
+                    }
+                }
+                if page == 1 || audio_mode == "Screen" || audio_mode == "Both" {
+                    if let Some(dev) = devices.iter().find(|d| d.description == selected_monitor) {
+                        sources.push((dev.name.clone(), false));
+                    }
+                }
+            }
+
+            for (device, is_mic) in sources {
+                let app_weak = app.as_weak();
+                let result = LevelMeter::start(&device, move |level| {
+                    let app_weak = app_weak.clone();
+                    let _ = slint::invoke_from_event_loop(move || {
+                        if let Some(app) = app_weak.upgrade() {
+                            let level = AudioLevel { peak: level.peak, rms: level.rms };
+                            if is_mic {
+                                app.set_mic_level(level);
+                            } else {
+                                app.set_monitor_level(level);
+                            }
+                        }
+                    });
+                });
+
+                match result {
+                    Ok(m) => {
+                        if let Ok(mut running) = meters.lock() {
+                            running.push(m);
+                        }
+                    }
+                    Err(e) => error!("Error starting level meter for {}: {}", device, e),
+                }
+            }
+        }
+    });
+
     app.on_choose_folder({
         let app_weak = app.as_weak();
         move || {
@@ -103,26 +642,229 @@ fn main() -> Result<(), Box<dyn Error>> {
                     let mut settings = Settings::load();
                     settings.save_path = path;
                     if let Err(e) = settings.save() {
-                        eprintln!("Error saving settings: {}", e);
+                        error!("Error saving settings: {}", e);
                     }
                 }
             }
         }
     });
     
+    app.on_choose_work_dir({
+        let app_weak = app.as_weak();
+        move || {
+            let Some(folder) = rfd::FileDialog::new()
+                .set_title("Choose Working Folder")
+                .pick_folder() else { return };
+            if let Some(app) = app_weak.upgrade() {
+                let path = folder.to_string_lossy().to_string();
+                app.set_work_dir(path.clone().into());
+
+                let mut settings = Settings::load();
+                settings.work_dir = path;
+                if let Err(e) = settings.save() {
+                    error!("Error saving settings: {}", e);
+                }
+            }
+        }
+    });
+
+    app.on_reset_work_dir({
+        let app_weak = app.as_weak();
+        move || {
+            if let Some(app) = app_weak.upgrade() {
+                app.set_work_dir("".into());
+            }
+            let mut settings = Settings::load();
+            settings.work_dir = String::new();
+            if let Err(e) = settings.save() {
+                error!("Error saving settings: {}", e);
+            }
+        }
+    });
+
     app.on_audio_mode_changed({
         move |mode| {
             let mut settings = Settings::load();
             settings.audio_mode = mode.to_string();
             if let Err(e) = settings.save() {
-                eprintln!("Error saving settings: {}", e);
+                error!("Error saving settings: {}", e);
+            }
+        }
+    });
+
+    app.on_auto_copy_changed({
+        move |enabled| {
+            let mut settings = Settings::load();
+            settings.auto_copy = enabled;
+            if let Err(e) = settings.save() {
+                error!("Error saving settings: {}", e);
+            }
+        }
+    });
+
+    app.on_metadata_sidecar_changed({
+        move |enabled| {
+            let mut settings = Settings::load();
+            settings.metadata_sidecar = enabled;
+            if let Err(e) = settings.save() {
+                error!("Error saving settings: {}", e);
+            }
+        }
+    });
+
+    app.on_separate_tracks_changed({
+        move |enabled| {
+            let mut settings = Settings::load();
+            settings.separate_tracks = enabled;
+            if let Err(e) = settings.save() {
+                error!("Error saving settings: {}", e);
+            }
+        }
+    });
+
+    app.on_noise_suppression_changed({
+        move |enabled| {
+            let mut settings = Settings::load();
+            settings.noise_suppression = enabled;
+            if let Err(e) = settings.save() {
+                error!("Error saving settings: {}", e);
+            }
+        }
+    });
+
+    app.on_loudnorm_changed({
+        let app_weak = app.as_weak();
+        move || {
+            if let Some(app) = app_weak.upgrade() {
+                let mut settings = Settings::load();
+                settings.loudnorm = app.get_loudnorm();
+                settings.loudness_target = app.get_loudness_target() as f32;
+                if let Err(e) = settings.save() {
+                    error!("Error saving settings: {}", e);
+                }
+            }
+        }
+    });
+
+    app.on_overlay_changed({
+        let app_weak = app.as_weak();
+        move || {
+            if let Some(app) = app_weak.upgrade() {
+                let mut settings = Settings::load();
+                settings.overlay_enabled = app.get_overlay_enabled();
+                settings.overlay_source = app.get_overlay_source().to_string();
+                settings.overlay_corner = label_to_slug(&app.get_overlay_corner());
+                settings.overlay_size = app.get_overlay_size() as u32;
+                if let Err(e) = settings.save() {
+                    error!("Error saving settings: {}", e);
+                }
+            }
+        }
+    });
+
+    app.on_choose_overlay_file({
+        let app_weak = app.as_weak();
+        move || {
+            if let Some(file) = rfd::FileDialog::new()
+                .set_title("Choose Overlay Video")
+                .add_filter("Video", &["mp4", "mkv", "webm", "mov", "gif"])
+                .pick_file() {
+                if let Some(app) = app_weak.upgrade() {
+                    let path = file.to_string_lossy().to_string();
+                    app.set_overlay_sources(overlay_sources(&path));
+                    app.set_overlay_source(path.into());
+                    app.invoke_overlay_changed();
+                }
+            }
+        }
+    });
+
+    app.on_watermark_changed({
+        let app_weak = app.as_weak();
+        move || {
+            if let Some(app) = app_weak.upgrade() {
+                let mut settings = Settings::load();
+                settings.watermark_enabled = app.get_watermark_enabled();
+                settings.watermark_kind = label_to_slug(&app.get_watermark_kind());
+                settings.watermark_text = app.get_watermark_text().to_string();
+                settings.watermark_image = app.get_watermark_image().to_string();
+                settings.watermark_font = app.get_watermark_font().to_string();
+                settings.watermark_font_size = app.get_watermark_font_size() as u32;
+                settings.watermark_position = label_to_slug(&app.get_watermark_position());
+                settings.watermark_opacity = app.get_watermark_opacity() as u32;
+                if let Err(e) = settings.save() {
+                    error!("Error saving settings: {}", e);
+                }
+            }
+        }
+    });
+
+    app.on_choose_watermark_image({
+        let app_weak = app.as_weak();
+        move || {
+            if let Some(file) = rfd::FileDialog::new()
+                .set_title("Choose Watermark Image")
+                .add_filter("Image", &["png", "jpg", "jpeg", "webp", "svg"])
+                .pick_file() {
+                if let Some(app) = app_weak.upgrade() {
+                    app.set_watermark_image(file.to_string_lossy().to_string().into());
+                    app.invoke_watermark_changed();
+                }
+            }
+        }
+    });
+
+    app.on_mic_volume_changed({
+        let recorder = recorder.clone();
+        move |percent| {
+            if let Ok(mut rec) = recorder.lock() {
+                rec.set_mic_volume(percent.round() as u32);
+            }
+        }
+    });
+
+    app.on_monitor_volume_changed({
+        let recorder = recorder.clone();
+        move |percent| {
+            if let Ok(mut rec) = recorder.lock() {
+                rec.set_monitor_volume(percent.round() as u32);
+            }
+        }
+    });
+
+    // Persist gains once the slider is let go rather than on every step
+    app.on_mix_volume_released({
+        let app_weak = app.as_weak();
+        move || {
+            if let Some(app) = app_weak.upgrade() {
+                let mut settings = Settings::load();
+                settings.mic_volume = app.get_mic_volume().round() as u32;
+                settings.monitor_volume = app.get_monitor_volume().round() as u32;
+                if let Err(e) = settings.save() {
+                    error!("Error saving settings: {}", e);
+                }
             }
         }
     });
 
-    // Check availability on startup
-    if !Recorder::is_available() {
-        eprintln!("wl-screenrec not found!");
+    app.on_toggle_mic_mute({
+        let recorder = recorder.clone();
+        let app_weak = app.as_weak();
+        move || {
+            if let Some(app) = app_weak.upgrade() {
+                let muted = !app.get_mic_muted();
+                if let Ok(mut rec) = recorder.lock() {
+                    rec.set_mic_muted(muted);
+                }
+                app.set_mic_muted(muted);
+            }
+        }
+    });
+
+    // Without wl-screenrec nothing works, so start on the diagnostics page
+    if !report.has("wl-screenrec") {
+        warn!("wl-screenrec not found!");
+        app.set_active_page(5);
     }
 
     app.on_start_recording({
@@ -135,26 +877,21 @@ fn main() -> Result<(), Box<dyn Error>> {
             let app = app_weak.upgrade().unwrap();
             let save_dir = app.get_save_path().to_string();
             let audio_mode = app.get_audio_mode().to_string();
-            
-            // Get selected devices
-            let selected_monitor = app.get_selected_monitor().to_string();
-            let selected_mic = app.get_selected_mic().to_string();
-            
-            // Resolve to internal names
-            let mut mic_arg = None;
-            let mut monitor_arg = None;
-            
-            if let Ok(devices) = audio_devices.lock() {
-                 if let Some(dev) = devices.iter().find(|d| d.description == selected_mic) {
-                     mic_arg = Some(dev.name.clone());
-                 }
-                 if let Some(dev) = devices.iter().find(|d| d.description == selected_monitor) {
-                     monitor_arg = Some(dev.name.clone());
-                 }
-            }
+            let separate_tracks = app.get_separate_tracks();
+
+            let devices = audio_devices.lock().map(|d| d.clone()).unwrap_or_default();
+            let source = match capture_source(&app, &geometry, &devices) {
+                Ok(source) => source,
+                Err(e) => {
+                    error!("Error starting recording: {}", e);
+                    app.set_geometry_error(e.into());
+                    app.set_active_page(2);
+                    return;
+                }
+            };
 
-            println!("Starting recording: mode={}, geometry={}, path={}, audio={}, mic={:?}, monitor={:?}", 
-                mode, geometry, save_dir, audio_mode, mic_arg, monitor_arg);
+            info!("Starting recording: mode={}, geometry={}, output={:?}, path={}, audio={}, mic={:?}, monitor={:?}", 
+                mode, geometry, source.output, save_dir, audio_mode, source.mic_device, source.monitor_device);
             
             let filename = format!("recording_{}.mp4", chrono::Local::now().format("%Y-%m-%d_%H-%M-%S"));
             let path = std::path::Path::new(&save_dir).join(filename);
@@ -165,17 +902,42 @@ fn main() -> Result<(), Box<dyn Error>> {
                 *last = Some(path_str.clone());
             }
 
-            let geo = if geometry.is_empty() { None } else { Some(geometry.as_str()) };
+            app.set_mic_muted(false);
+            app.set_marker_count(0);
+            app.set_disk_warning("".into());
 
             if let Ok(mut rec) = recorder.lock() {
                 // Save settings (including current audio mode) when starting recording
                 let mut current_settings = Settings::load();
                 current_settings.save_path = save_dir.clone();
                 current_settings.audio_mode = audio_mode.clone();
+                current_settings.recording_mode = mode.to_string();
+                current_settings.recording_geometry = geometry.to_string();
+                current_settings.recording_output = app.get_recording_output().to_string();
                 let _ = current_settings.save();
 
-                if let Err(e) = rec.start_session(&path_str, geo, &audio_mode, mic_arg.as_deref(), monitor_arg.as_deref()) {
-                    eprintln!("Error starting recording: {}", e);
+                // Everything done after capture runs through ffmpeg
+                let has_ffmpeg = app.get_has_ffmpeg();
+                let config = RecordingConfig {
+                    source,
+                    separate_tracks: separate_tracks && has_ffmpeg,
+                    noise_suppression: app.get_noise_suppression() && has_ffmpeg,
+                    loudness_target: (app.get_loudnorm() && has_ffmpeg).then(|| app.get_loudness_target() as f32),
+                    overlay: (app.get_overlay_enabled() && has_ffmpeg && !app.get_overlay_source().is_empty()).then(|| OverlayConfig {
+                        source: app.get_overlay_source().to_string(),
+                        corner: label_to_slug(&app.get_overlay_corner()),
+                        size: app.get_overlay_size() as u32,
+                    }),
+                    watermark: watermark_config(&app).filter(|_| has_ffmpeg),
+                    sidecar: app.get_metadata_sidecar(),
+                    work_dir: disk::work_dir(&app.get_work_dir(), &save_dir),
+                    min_free: current_settings.min_free_mb * 1024 * 1024,
+                    final_path: path_str,
+                };
+
+                if let Err(e) = rec.start_session(config) {
+                    error!("Error starting recording: {}", e);
+                    show_error(&app, &e, 0);
                 }
             }
         }
@@ -184,6 +946,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     app.on_toggle_pause({
         let recorder = recorder.clone();
         let app_weak = app.as_weak();
+        let audio_devices = audio_devices.clone();
         move || {
             if let Some(app) = app_weak.upgrade() {
                 let is_paused = app.get_is_paused(); 
@@ -192,14 +955,32 @@ fn main() -> Result<(), Box<dyn Error>> {
                     if !is_paused {
                         // Pause
                         if let Err(e) = rec.pause_session() {
-                            eprintln!("Error pausing: {}", e);
+                            error!("Error pausing: {}", e);
                         } else {
                             app.set_is_paused(true);
                         }
                     } else {
-                        // Resume
+                        // Resume, picking up any source changes made while paused
+                        let devices = audio_devices.lock().map(|d| d.clone()).unwrap_or_default();
+                        let source = match capture_source(&app, &app.get_recording_geometry(), &devices) {
+                            Ok(source) => source,
+                            Err(e) => {
+                                error!("Error resuming: {}", e);
+                                app.set_geometry_error(e.into());
+                                app.set_active_page(2);
+                                return;
+                            }
+                        };
+                        if let Err(e) = rec.change_source(source) {
+                            error!("Error changing capture source: {}", e);
+                            show_error(&app, &e, 3);
+                            return;
+                        }
+
                         if let Err(e) = rec.resume_session() {
-                             eprintln!("Error resuming: {}", e);
+                             error!("Error resuming: {}", e);
+                             // The session stays paused, so stopping still saves what was recorded
+                             show_error(&app, &e, 3);
                         } else {
                             app.set_is_paused(false);
                         }
@@ -209,42 +990,258 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
     });
 
+    // Chapters of the last recording, renamed on the finished page
+    let last_chapters = Arc::new(Mutex::new(Vec::<Chapter>::new()));
+
+    app.on_add_marker({
+        let recorder = recorder.clone();
+        let app_weak = app.as_weak();
+        move || {
+            if let (Some(app), Ok(mut rec)) = (app_weak.upgrade(), recorder.lock()) {
+                match rec.add_marker() {
+                    Ok(count) => app.set_marker_count(count as i32),
+                    Err(e) => error!("Error adding marker: {}", e),
+                }
+            }
+        }
+    });
+
+    // Watch the working folder's disk while recording: warn when space runs
+    // low and pause before it is too full to finish the recording
+    let disk_timer = slint::Timer::default();
+    disk_timer.start(slint::TimerMode::Repeated, std::time::Duration::from_secs(5), {
+        let recorder = recorder.clone();
+        let app_weak = app.as_weak();
+        move || {
+            let Some(app) = app_weak.upgrade() else { return };
+            if app.get_active_page() != 3 || app.get_is_paused() {
+                return;
+            }
+            // Never wait on the UI thread, the next tick checks again
+            let Ok(mut rec) = recorder.try_lock() else { return };
+            let Some(usage) = rec.disk_usage() else { return };
+
+            match usage.level() {
+                SpaceLevel::Ok => app.set_disk_warning("".into()),
+                SpaceLevel::Low => app.set_disk_warning(format!("Low disk space: {} left", disk::format_size(usage.available)).into()),
+                SpaceLevel::Critical => {
+                    warn!("Pausing, only {} left and finishing needs about {}",
+                        disk::format_size(usage.available), disk::format_size(usage.reserved));
+                    match rec.pause_session() {
+                        Ok(()) => {
+                            app.set_is_paused(true);
+                            app.set_disk_warning(format!("Paused: disk almost full ({} left)", disk::format_size(usage.available)).into());
+                        }
+                        Err(e) => error!("Error pausing: {}", e),
+                    }
+                }
+            }
+        }
+    });
+
+    // The worker saving the last recording, waited for on exit so closing the
+    // window can't cut it off halfway
+    let finish_worker = Arc::new(Mutex::new(None::<std::thread::JoinHandle<()>>));
+
     app.on_stop_recording({
+        let finish_worker = finish_worker.clone();
         let recorder = recorder.clone();
         let app_weak = app.as_weak();
         let last_path = last_path.clone();
+        let last_chapters = last_chapters.clone();
         move || {
-            if let Some(app) = app_weak.upgrade() {
-                app.set_is_paused(false);
+            let Some(app) = app_weak.upgrade() else { return };
+            app.set_is_paused(false);
+            // Joining and post-processing can take minutes, so the page says
+            // so and fills in the results once the worker is done
+            app.set_finishing(true);
+            app.set_loudness_summary("".into());
+            app.set_skipped_stages("".into());
+            app.set_copied_to_clipboard(false);
+            app.set_last_thumbnail(slint::Image::default());
+            apply_chapters(&app, &[]);
+            let auto_copy = app.get_auto_copy() && app.get_has_wl_copy();
+
+            let recorder = recorder.clone();
+            let app_weak = app_weak.clone();
+            let last_path = last_path.clone();
+            let last_chapters = last_chapters.clone();
+            let worker = std::thread::spawn(move || {
+                let Ok(mut rec) = recorder.lock() else { return };
+                let finished = rec.finish_session();
+                let (saved, loudness, skipped, chapters) =
+                    (rec.last_output(), rec.last_loudness(), rec.last_skipped(), rec.last_chapters());
+                drop(rec);
+
+                if let Err(e) = finished {
+                    error!("Error finishing recording: {}", e);
+                    let _ = slint::invoke_from_event_loop(move || {
+                        if let Some(app) = app_weak.upgrade() {
+                            app.set_finishing(false);
+                            show_error(&app, &e, 0);
+                        }
+                    });
+                    return;
+                }
+
+                // A suffix is added when the name was already taken
+                let video_path = saved.map(|p| p.to_string_lossy().to_string());
+                if let Ok(mut last) = last_path.lock() {
+                    *last = video_path.clone();
+                }
+                if let Ok(mut store) = last_chapters.lock() {
+                    *store = chapters.clone();
+                }
+
+                let copied = match (&video_path, auto_copy) {
+                    (Some(path), true) => clipboard::copy_recording(path)
+                        .map_err(|e| error!("Error copying to clipboard: {}", e))
+                        .is_ok(),
+                    _ => false,
+                };
+
+                let app_weak_results = app_weak.clone();
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(app) = app_weak_results.upgrade() {
+                        let summary = loudness
+                            .map(|l| format!("{:.1} LUFS, {:.1} dBTP", l.integrated, l.true_peak))
+                            .unwrap_or_default();
+                        app.set_loudness_summary(summary.into());
+                        app.set_skipped_stages(skipped.join("\n").into());
+                        apply_chapters(&app, &chapters);
+                        app.set_copied_to_clipboard(copied);
+                        app.set_finishing(false);
+                    }
+                });
+
+                // Recording stopped successfully, generate thumbnail
+                let Some(video_path) = video_path else { return };
+                let thumb_path = instance::temp_path("thumb.jpg");
+                let mut cmd = std::process::Command::new("ffmpeg");
+                cmd.args(["-y", "-i", &video_path, "-ss", "00:00:01", "-vframes", "1"]).arg(&thumb_path);
+                logging::command(&cmd);
+                if let Ok(output) = cmd.output() {
+                    logging::output("ffmpeg", &output);
+                }
+
+                // Load image inside the event loop because slint::Image is not Send
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Ok(img) = slint::Image::load_from_path(&thumb_path) {
+                        if let Some(app) = app_weak.upgrade() {
+                            app.set_last_thumbnail(img);
+                        }
+                    }
+                });
+            });
+            if let Ok(mut handle) = finish_worker.lock() {
+                *handle = Some(worker);
             }
+        }
+    });
 
-            if let Ok(mut rec) = recorder.lock() {
-                if let Err(e) = rec.finish_session() {
-                    eprintln!("Error finishing recording: {}", e);
-                } else {
-                    // Recording stopped successfully, generate thumbnail
-                    let path_opt = last_path.lock().unwrap().clone();
-                    if let Some(video_path) = path_opt {
-                        let app_weak_thumb = app_weak.clone();
-                        // Run thumbnail generation in background
-                        std::thread::spawn(move || {
-                            let thumb_path = "/tmp/roton_thumb.jpg";
-                            let _ = std::process::Command::new("ffmpeg")
-                                .args(&["-y", "-i", &video_path, "-ss", "00:00:01", "-vframes", "1", thumb_path])
-                                .output();
-                            
-                            // Load image inside the event loop because slint::Image is not Send
-                            let _ = slint::invoke_from_event_loop(move || {
-                                if let Ok(img) = slint::Image::load_from_path(std::path::Path::new(thumb_path)) {
-                                    if let Some(app) = app_weak_thumb.upgrade() {
-                                        app.set_last_thumbnail(img);
-                                    }
-                                }
-                            });
-                        });
+    // Region presets, re-validated against the current outputs whenever the list is shown
+    let refresh_region_presets = {
+        let app_weak = app.as_weak();
+        move || {
+            let presets = Settings::load().region_presets;
+            let outputs = geometry::outputs().unwrap_or_else(|e| {
+                error!("Error validating region presets: {}", e);
+                Vec::new()
+            });
+
+            let entries: Vec<PresetEntry> = presets
+                .iter()
+                .map(|preset| {
+                    // Without output info we can only check the format
+                    let error = preset.geometry.parse::<Geometry>()
+                        .and_then(|g| if outputs.is_empty() { Ok(()) } else { g.validate_on(&outputs) })
+                        .err()
+                        .unwrap_or_default();
+                    PresetEntry {
+                        name: preset.name.clone().into(),
+                        geometry: preset.geometry.clone().into(),
+                        valid: error.is_empty(),
+                        error: error.into(),
                     }
+                })
+                .collect();
+
+            if let Some(app) = app_weak.upgrade() {
+                app.set_region_presets(std::rc::Rc::new(slint::VecModel::from(entries)).into());
+            }
+        }
+    };
+
+    app.on_refresh_region_presets(refresh_region_presets.clone());
+
+    // Outputs for full screen recordings; without sway or Hyprland only the default is offered
+    app.on_refresh_outputs({
+        let app_weak = app.as_weak();
+        move || {
+            let Some(app) = app_weak.upgrade() else { return };
+            let names = compositor::list_outputs().unwrap_or_else(|e| {
+                info!("Not listing outputs: {}", e);
+                Vec::new()
+            });
+
+            let current = app.get_recording_output().to_string();
+            if !current.is_empty() && !names.contains(&current) {
+                warn!("Output {} is gone, using the default", current);
+                app.set_recording_output("".into());
+            }
+
+            let entries: Vec<slint::SharedString> = std::iter::once("Default Output".to_string())
+                .chain(names)
+                .map(Into::into)
+                .collect();
+            app.set_outputs(std::rc::Rc::new(slint::VecModel::from(entries)).into());
+        }
+    });
+
+    app.on_save_region_preset({
+        let app_weak = app.as_weak();
+        let refresh_region_presets = refresh_region_presets.clone();
+        move |name| {
+            if let Some(app) = app_weak.upgrade() {
+                let geometry = app.get_recording_geometry().to_string();
+                let name = name.trim().to_string();
+                if geometry.is_empty() || name.is_empty() {
+                    return;
+                }
+
+                // Saving under an existing name replaces that preset
+                let mut settings = Settings::load();
+                settings.region_presets.retain(|p| p.name != name);
+                settings.region_presets.push(RegionPreset { name, geometry });
+                if let Err(e) = settings.save() {
+                    error!("Error saving settings: {}", e);
+                }
+            }
+            refresh_region_presets();
+        }
+    });
+
+    app.on_region_constraint_changed({
+        move |constraint| {
+            let mut settings = Settings::load();
+            settings.region_constraint = constraint.to_string();
+            if let Err(e) = settings.save() {
+                error!("Error saving settings: {}", e);
+            }
+        }
+    });
+
+    app.on_delete_region_preset({
+        let refresh_region_presets = refresh_region_presets.clone();
+        move |index| {
+            let mut settings = Settings::load();
+            if index >= 0 && (index as usize) < settings.region_presets.len() {
+                settings.region_presets.remove(index as usize);
+                if let Err(e) = settings.save() {
+                    error!("Error saving settings: {}", e);
                 }
             }
+            refresh_region_presets();
         }
     });
 
@@ -259,17 +1256,92 @@ fn main() -> Result<(), Box<dyn Error>> {
                 let output = std::process::Command::new("slurp")
                     .output();
                 
+                let mut page = area_return_page(&app);
                 if let Ok(out) = output {
                     if out.status.success() {
-                        let geo = String::from_utf8_lossy(&out.stdout).trim().to_string();
-                        app.set_recording_mode("selection".into());
-                        app.set_recording_geometry(geo.into());
+                        let raw = String::from_utf8_lossy(&out.stdout).trim().to_string();
+                        if !apply_region(&app, &raw, "selection", region_constraint(&app)) {
+                            page = 2;
+                        }
                     }
                 }
                 
-                // Show app again and go home
+                // Show app again and go back
+                app.show().unwrap();
+                app.set_active_page(page);
+            }
+        }
+    });
+
+    // Windows last listed on the screen page, indexed by the UI
+    let windows = Arc::new(Mutex::new(Vec::<WindowInfo>::new()));
+
+    app.on_list_windows({
+        let app_weak = app.as_weak();
+        let windows = windows.clone();
+        move || {
+            if let Some(app) = app_weak.upgrade() {
+                match compositor::list_windows() {
+                    Ok(list) => {
+                        let labels: Vec<slint::SharedString> = list
+                            .iter()
+                            .map(|w| format!("{} ({})", w.label(), w.geometry).into())
+                            .collect();
+                        app.set_window_list(std::rc::Rc::new(slint::VecModel::from(labels)).into());
+                        app.set_geometry_error("".into());
+                        if let Ok(mut store) = windows.lock() {
+                            *store = list;
+                        }
+                    }
+                    Err(e) => {
+                        error!("Error listing windows: {}", e);
+                        app.set_geometry_error(e.into());
+                    }
+                }
+            }
+        }
+    });
+
+    app.on_select_window({
+        let app_weak = app.as_weak();
+        let windows = windows.clone();
+        move |index| {
+            let window = windows.lock().ok().and_then(|w| w.get(index as usize).cloned());
+            if let (Some(app), Some(window)) = (app_weak.upgrade(), window) {
+                if apply_region(&app, &window.geometry.to_string(), "window", SizeConstraint::Free) {
+                    app.set_active_page(area_return_page(&app));
+                }
+            }
+        }
+    });
+
+    app.on_pick_window({
+        let app_weak = app.as_weak();
+        let windows = windows.clone();
+        move || {
+            if let Some(app) = app_weak.upgrade() {
+                let list = windows.lock().map(|w| w.clone()).unwrap_or_default();
+
+                // Hide app for slurp
+                app.hide().unwrap();
+
+                let mut page = area_return_page(&app);
+                match compositor::slurp_windows(&list) {
+                    Ok(Some(raw)) => {
+                        if !apply_region(&app, &raw, "window", region_constraint(&app)) {
+                            page = 2;
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        error!("Error picking window: {}", e);
+                        app.set_geometry_error(e.into());
+                        page = 2;
+                    }
+                }
+
                 app.show().unwrap();
-                app.set_active_page(0);
+                app.set_active_page(page);
             }
         }
     });
@@ -286,7 +1358,111 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
     });
 
-    app.run()?;
+    app.on_copy_recording({
+        let app_weak = app.as_weak();
+        let last_path = last_path.clone();
+        move || {
+            let path_opt = last_path.lock().unwrap().clone();
+            if let (Some(app), Some(video_path)) = (app_weak.upgrade(), path_opt) {
+                match clipboard::copy_recording(&video_path) {
+                    Ok(()) => app.set_copied_to_clipboard(true),
+                    Err(e) => error!("Error copying to clipboard: {}", e),
+                }
+            }
+        }
+    });
+
+    app.on_rename_chapter({
+        let last_chapters = last_chapters.clone();
+        move |index, title| {
+            // Only the stored copy changes, the model would steal focus from the field
+            if let Ok(mut chapters) = last_chapters.lock() {
+                if let Some(chapter) = chapters.get_mut(index as usize) {
+                    chapter.title = title.to_string();
+                }
+            }
+        }
+    });
+
+    app.on_save_chapters({
+        let app_weak = app.as_weak();
+        let last_path = last_path.clone();
+        let last_chapters = last_chapters.clone();
+        move || {
+            let path_opt = last_path.lock().unwrap().clone();
+            let chapters = last_chapters.lock().map(|c| c.clone()).unwrap_or_default();
+            if let (Some(app), Some(video_path)) = (app_weak.upgrade(), path_opt) {
+                let status = match chapters::update(&video_path, &chapters) {
+                    Ok(()) => "Chapters saved".to_string(),
+                    Err(e) => {
+                        error!("Error saving chapters: {}", e);
+                        e
+                    }
+                };
+                app.set_chapters_status(status.into());
+            }
+        }
+    });
+
+    app.on_copy_chapters({
+        let app_weak = app.as_weak();
+        let last_chapters = last_chapters.clone();
+        move || {
+            let chapters = last_chapters.lock().map(|c| c.clone()).unwrap_or_default();
+            if let Some(app) = app_weak.upgrade() {
+                let status = match clipboard::copy_text(&chapters::timestamp_list(&chapters)) {
+                    Ok(()) => "Timestamps copied".to_string(),
+                    Err(e) => {
+                        error!("Error copying timestamps: {}", e);
+                        e
+                    }
+                };
+                app.set_chapters_status(status.into());
+            }
+        }
+    });
+
+    app.on_export_chapters({
+        let app_weak = app.as_weak();
+        let last_path = last_path.clone();
+        let last_chapters = last_chapters.clone();
+        move || {
+            let Some(video_path) = last_path.lock().unwrap().clone() else { return };
+            let video_path = std::path::Path::new(&video_path);
+            let stem = video_path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+            let mut dialog = rfd::FileDialog::new()
+                .set_title("Export Chapters")
+                .add_filter("Text", &["txt"])
+                .set_file_name(format!("{}-chapters.txt", stem));
+            if let Some(dir) = video_path.parent() {
+                dialog = dialog.set_directory(dir);
+            }
+            let Some(file) = dialog.save_file() else { return };
+
+            let chapters = last_chapters.lock().map(|c| c.clone()).unwrap_or_default();
+            let status = match std::fs::write(&file, chapters::timestamp_list(&chapters)) {
+                Ok(()) => format!("Exported to {}", file.display()),
+                Err(e) => {
+                    error!("Error exporting chapters: {}", e);
+                    e.to_string()
+                }
+            };
+            if let Some(app) = app_weak.upgrade() {
+                app.set_chapters_status(status.into());
+            }
+        }
+    });
+
+    let result = app.run();
+    let worker = finish_worker.lock().ok().and_then(|mut handle| handle.take());
+    if let Some(worker) = worker.filter(|w| !w.is_finished()) {
+        info!("Waiting for the last recording to be saved");
+        let _ = worker.join();
+    }
+    if is_primary {
+        instance::release();
+    }
+    result?;
 
     Ok(())
 }
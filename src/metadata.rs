@@ -0,0 +1,93 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use log::info;
+use serde::Serialize;
+
+use crate::chapters::Chapter;
+use crate::output;
+use crate::recorder::{CaptureSource, RecordingConfig};
+
+/// Where one segment ended up in the joined file
+#[derive(Clone, Serialize)]
+pub struct SegmentTiming {
+    pub started: String, // wall clock, RFC 3339
+    pub offset: f64,     // seconds into the file
+    pub duration: f64,
+    pub markers: Vec<f64>, // seconds into the file
+    pub source: CaptureSource,
+}
+
+#[derive(Serialize)]
+struct Sidecar<'a> {
+    file: String,
+    created: String,
+    encoder: String,
+    duration: f64,
+    config: &'a RecordingConfig,
+    segments: &'a [SegmentTiming],
+    chapters: &'a [Chapter],
+}
+
+fn encoder() -> String {
+    format!("Roton {} (wl-screenrec)", env!("CARGO_PKG_VERSION"))
+}
+
+fn timestamp(time: DateTime<Local>) -> String {
+    time.with_timezone(&Utc).to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+/// Container tags describing when and by what the recording was made. mp4
+/// has no atoms for capture details like the region or audio devices, those
+/// go to the JSON sidecar.
+pub fn tags(started: DateTime<Local>) -> Vec<(String, String)> {
+    [
+        ("title", format!("Screen recording {}", started.format("%Y-%m-%d %H:%M"))),
+        ("date", started.format("%Y-%m-%d").to_string()),
+        ("creation_time", timestamp(started)),
+        ("encoder", encoder()),
+    ]
+    .into_iter()
+    .map(|(key, value)| (key.to_string(), value))
+    .collect()
+}
+
+/// The JSON file written next to `video`
+pub fn sidecar_path(video: &Path) -> PathBuf {
+    video.with_extension("json")
+}
+
+/// Write the full recording config and segment timing next to the video
+pub fn write_sidecar(
+    video: &Path,
+    started: DateTime<Local>,
+    config: &RecordingConfig,
+    segments: &[SegmentTiming],
+    chapters: &[Chapter],
+) -> Result<PathBuf, String> {
+    let sidecar = Sidecar {
+        file: video.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default(),
+        created: timestamp(started),
+        encoder: encoder(),
+        duration: segments.last().map(|s| s.offset + s.duration).unwrap_or_default(),
+        config,
+        segments,
+        chapters,
+    };
+    let content = serde_json::to_string_pretty(&sidecar).map_err(|e| e.to_string())?;
+
+    // Never replace a file that is already there, a suffix is added instead
+    let (path, mut file) = loop {
+        let path = output::unique_path(&sidecar_path(video));
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => break (path, file),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(format!("Failed to create {}: {}", path.display(), e)),
+        }
+    };
+    file.write_all(content.as_bytes()).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    info!("Wrote recording details to {}", path.display());
+    Ok(path)
+}
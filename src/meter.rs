@@ -0,0 +1,102 @@
+use std::io::Read;
+use std::process::{Child, Command, Stdio};
+use std::thread::JoinHandle;
+
+const SAMPLE_RATE: usize = 16000;
+// 50ms of mono s16le audio per reading
+const CHUNK_BYTES: usize = SAMPLE_RATE / 20 * 2;
+// Anything quieter than this shows as an empty meter
+const FLOOR_DB: f32 = -60.0;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Level {
+    pub peak: f32, // 0.0 - 1.0 on a dBFS scale
+    pub rms: f32,  // 0.0 - 1.0 on a dBFS scale
+}
+
+// Map a linear amplitude to 0.0 - 1.0 across FLOOR_DB..0 dBFS
+fn to_meter_scale(amplitude: f32) -> f32 {
+    if amplitude <= 0.0 {
+        return 0.0;
+    }
+    let db = 20.0 * amplitude.log10();
+    ((db - FLOOR_DB) / -FLOOR_DB).clamp(0.0, 1.0)
+}
+
+fn measure(chunk: &[u8]) -> Level {
+    let mut peak = 0.0f32;
+    let mut sum_sq = 0.0f64;
+    let mut count = 0usize;
+
+    for sample in chunk.chunks_exact(2) {
+        let value = i16::from_le_bytes([sample[0], sample[1]]) as f32 / i16::MAX as f32;
+        peak = peak.max(value.abs());
+        sum_sq += (value as f64) * (value as f64);
+        count += 1;
+    }
+
+    let rms = if count > 0 { (sum_sq / count as f64).sqrt() as f32 } else { 0.0 };
+
+    Level {
+        peak: to_meter_scale(peak),
+        rms: to_meter_scale(rms),
+    }
+}
+
+/// Reads raw PCM from a PulseAudio source with `parec` on a background thread
+/// and reports peak/RMS levels. The capture stops when the meter is dropped.
+pub struct LevelMeter {
+    process: Option<Child>,
+    reader: Option<JoinHandle<()>>,
+}
+
+impl LevelMeter {
+    pub fn start<F>(device: &str, on_level: F) -> Result<Self, String>
+    where
+        F: Fn(Level) + Send + 'static,
+    {
+        let mut child = Command::new("parec")
+            .arg("--raw")
+            .arg(format!("--device={}", device))
+            .arg("--format=s16le")
+            .arg("--channels=1")
+            .arg(format!("--rate={}", SAMPLE_RATE))
+            .arg("--latency-msec=50")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Failed to start parec: {}", e))?;
+
+        let mut stdout = child.stdout.take().ok_or("Failed to capture parec output")?;
+
+        let reader = std::thread::spawn(move || {
+            let mut chunk = vec![0u8; CHUNK_BYTES];
+            // read_exact fails once parec is killed and the pipe closes
+            while stdout.read_exact(&mut chunk).is_ok() {
+                on_level(measure(&chunk));
+            }
+            on_level(Level::default());
+        });
+
+        Ok(Self {
+            process: Some(child),
+            reader: Some(reader),
+        })
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(mut child) = self.process.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        if let Some(reader) = self.reader.take() {
+            let _ = reader.join();
+        }
+    }
+}
+
+impl Drop for LevelMeter {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -0,0 +1,90 @@
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use log::{info, warn};
+
+/// `path` itself if nothing is there yet, otherwise the first free
+/// "name-1.ext", "name-2.ext", ... next to it
+pub fn unique_path(path: &Path) -> PathBuf {
+    if !path.exists() {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let extension = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    (1..)
+        .map(|n| path.with_file_name(format!("{}-{}{}", stem, n, extension)))
+        .find(|candidate| !candidate.exists())
+        .unwrap()
+}
+
+/// Move a finished recording to `target` without ever replacing an existing
+/// file. It is first written to a hidden ".part" file in the destination
+/// folder and synced, then linked into place under a free name, so the
+/// destination only ever holds complete recordings. `session` keeps the
+/// ".part" file apart from another instance saving under the same name.
+/// Returns the path used.
+pub fn finalize(staged: &Path, target: &Path, session: &str) -> io::Result<PathBuf> {
+    let dir = target.parent().unwrap_or(Path::new("."));
+    let name = target.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let part = dir.join(format!(".{}.{}.part", name, session));
+
+    let result = place_part(staged, &part).and_then(|_| link_unique(&part, target));
+    if result.is_err() {
+        let _ = fs::remove_file(&part);
+    }
+    let path = result?;
+
+    sync_dir(dir);
+    info!("Saved recording to {}", path.display());
+    Ok(path)
+}
+
+/// Replace `target` with a rewritten copy of it staged in the same folder,
+/// synced first so a crash leaves either the old or the new file
+pub fn replace(staged: &Path, target: &Path) -> io::Result<()> {
+    File::open(staged)?.sync_all()?;
+    fs::rename(staged, target)?;
+    sync_dir(target.parent().unwrap_or(Path::new(".")));
+    Ok(())
+}
+
+// Make new or renamed directory entries durable too
+fn sync_dir(dir: &Path) {
+    if let Err(e) = File::open(dir).and_then(|d| d.sync_all()) {
+        warn!("Could not sync {}: {}", dir.display(), e);
+    }
+}
+
+fn place_part(staged: &Path, part: &Path) -> io::Result<()> {
+    // Rename when the working folder shares the filesystem, copy otherwise
+    if let Err(e) = fs::rename(staged, part) {
+        if e.raw_os_error() != Some(18) { // EXDEV: Invalid cross-device link
+            return Err(e);
+        }
+        fs::copy(staged, part)?;
+        fs::remove_file(staged)?;
+    }
+    File::open(part)?.sync_all()
+}
+
+fn link_unique(part: &Path, target: &Path) -> io::Result<PathBuf> {
+    loop {
+        let candidate = unique_path(target);
+        // A hard link fails instead of replacing a file that appeared since
+        // the check. Filesystems without hard links (FAT, some network
+        // mounts) fall back to a plain rename.
+        match fs::hard_link(part, &candidate) {
+            Ok(()) => {
+                fs::remove_file(part)?;
+                return Ok(candidate);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => {
+                warn!("Could not link {}, renaming instead: {}", candidate.display(), e);
+                fs::rename(part, &candidate)?;
+                return Ok(candidate);
+            }
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use std::fs;
+use std::process::{Child, Command, Stdio};
+
+use serde::Serialize;
+
+use crate::logging;
+
+// Distance between the overlay and the edges of the screen capture
+const MARGIN: u32 = 20;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OverlayConfig {
+    pub source: String, // V4L2 device (/dev/videoN) or a video file
+    pub corner: String, // "top-left", "top-right", "bottom-left" or "bottom-right"
+    pub size: u32,      // Overlay height as a percentage of the capture height
+}
+
+impl OverlayConfig {
+    /// Devices are captured alongside each segment, files are only read on finish
+    pub fn is_device(&self) -> bool {
+        self.source.starts_with("/dev/video")
+    }
+
+    fn position(&self) -> String {
+        let x = if self.corner.ends_with("right") { format!("W-w-{}", MARGIN) } else { MARGIN.to_string() };
+        let y = if self.corner.starts_with("bottom") { format!("H-h-{}", MARGIN) } else { MARGIN.to_string() };
+        format!("x={}:y={}", x, y)
+    }
+}
+
+pub fn list_video_devices() -> Vec<String> {
+    let mut devices: Vec<String> = fs::read_dir("/dev")
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.path().to_string_lossy().to_string())
+                .filter(|p| p.starts_with("/dev/video"))
+                .collect()
+        })
+        .unwrap_or_default();
+    devices.sort();
+    devices
+}
+
+/// Record a V4L2 device to `output` until interrupted
+pub fn start_capture(device: &str, output: &str) -> Result<Child, String> {
+    let mut cmd = Command::new("ffmpeg");
+    cmd.arg("-nostats").arg("-loglevel").arg("warning")
+        .arg("-f").arg("v4l2")
+        .arg("-i").arg(device)
+        .arg("-c:v").arg("libx264")
+        .arg("-preset").arg("ultrafast")
+        .arg("-pix_fmt").arg("yuv420p")
+        .arg("-y")
+        .arg(output)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+    logging::command(&cmd);
+    let mut child = cmd.spawn()
+        .map_err(|e| format!("Failed to start overlay capture: {}", e))?;
+    logging::follow_stderr("ffmpeg", &mut child);
+    Ok(child)
+}
+
+/// Input options for reading the overlay source
+pub fn input_args(config: &OverlayConfig) -> &'static [&'static str] {
+    if config.is_device() {
+        &[]
+    } else {
+        // Loop a file source so it covers the whole recording
+        &["-stream_loop", "-1"]
+    }
+}
+
+/// Filter placing input `index` over the `base` stream, labelled `out`
+pub fn filter(config: &OverlayConfig, index: usize, base: &str, out: &str) -> String {
+    format!(
+        "[{}:v][{}]scale2ref=w=oh*dar:h=main_h*{}/100[pip][base];[base][pip]overlay={}:eof_action=pass:shortest={}[{}]",
+        index,
+        base,
+        config.size,
+        config.position(),
+        if config.is_device() { 0 } else { 1 },
+        out,
+    )
+}
@@ -0,0 +1,81 @@
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use serde::Deserialize;
+
+use crate::logging;
+
+/// The stream parameters that have to match for the concat demuxer to join
+/// files with `-c copy`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct StreamInfo {
+    pub codec_type: String,
+    #[serde(default)]
+    pub codec_name: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub pix_fmt: Option<String>,
+    pub sample_rate: Option<String>, // ffprobe reports it as a string
+    pub channels: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MediaInfo {
+    pub streams: Vec<StreamInfo>,
+    pub duration: f64, // seconds
+}
+
+#[derive(Deserialize)]
+struct ProbeFormat {
+    duration: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ProbeOutput {
+    #[serde(default)]
+    streams: Vec<StreamInfo>,
+    format: Option<ProbeFormat>,
+}
+
+impl MediaInfo {
+    pub fn video(&self) -> Option<&StreamInfo> {
+        self.streams.iter().find(|s| s.codec_type == "video")
+    }
+
+    pub fn audio(&self) -> Option<&StreamInfo> {
+        self.streams.iter().find(|s| s.codec_type == "audio")
+    }
+}
+
+pub fn probe(path: &Path) -> Result<MediaInfo, String> {
+    let mut cmd = Command::new("ffprobe");
+    cmd.arg("-v").arg("error")
+        .arg("-show_entries")
+        .arg("stream=codec_type,codec_name,width,height,pix_fmt,sample_rate,channels:format=duration")
+        .arg("-of").arg("json")
+        .arg(path)
+        .stdin(Stdio::null());
+    logging::command(&cmd);
+    let output = cmd.output().map_err(|e| format!("Failed to run ffprobe: {}", e))?;
+    logging::output("ffprobe", &output);
+
+    if !output.status.success() {
+        return Err(format!("ffprobe could not read {}", path.display()));
+    }
+    let parsed: ProbeOutput = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Invalid ffprobe output for {}: {}", path.display(), e))?;
+
+    Ok(MediaInfo {
+        streams: parsed.streams,
+        duration: parsed.format
+            .and_then(|f| f.duration)
+            .and_then(|d| d.parse().ok())
+            .unwrap_or(0.0),
+    })
+}
+
+/// Whether the files share the same stream layout and parameters, so they
+/// can be joined without re-encoding
+pub fn can_stream_copy(infos: &[MediaInfo]) -> bool {
+    infos.windows(2).all(|pair| pair[0].streams == pair[1].streams)
+}
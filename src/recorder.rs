@@ -1,251 +1,1034 @@
 use std::process::{Child, Command, Stdio};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
 use std::fs;
+use std::time::{Duration, Instant};
 
-#[derive(Clone)]
-struct RecordingConfig {
-    geometry: Option<String>,
-    audio_mode: String,
-    mic_device: Option<String>,
-    monitor_device: Option<String>,
-    final_path: String,
+use crate::chapters::{self, Chapter};
+use crate::loudness::{self, LoudnessStats};
+use crate::overlay::{self, OverlayConfig};
+use crate::watermark::WatermarkConfig;
+use crate::compose;
+use crate::geometry::Geometry;
+use crate::disk::{self, DiskUsage};
+use crate::output;
+use crate::instance;
+use crate::error::RecorderError;
+use crate::logging;
+use crate::probe::{self, MediaInfo};
+use crate::metadata::{self, SegmentTiming};
+use chrono::{DateTime, Local};
+use log::{error, info, warn};
+use serde::Serialize;
+
+// Run a pactl command, logged like every other child process
+fn pactl(args: &[&str]) -> Option<std::process::Output> {
+    let mut cmd = Command::new("pactl");
+    cmd.args(args);
+    logging::command(&cmd);
+    match cmd.output() {
+        Ok(output) => {
+            logging::output("pactl", &output);
+            Some(output)
+        }
+        Err(e) => {
+            warn!("Failed to run pactl: {}", e);
+            None
+        }
+    }
+}
+
+/// What gets captured. Can be changed between segments while paused.
+#[derive(Clone, PartialEq, Serialize)]
+pub struct CaptureSource {
+    pub geometry: Option<Geometry>,
+    pub output: Option<String>, // only used for full screen recordings
+    pub audio_mode: String,
+    pub mic_device: Option<String>,
+    pub monitor_device: Option<String>,
+}
+
+impl CaptureSource {
+    fn has_mic(&self) -> bool {
+        self.audio_mode == "Mic" || self.audio_mode == "Both"
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct RecordingConfig {
+    pub source: CaptureSource,
+    pub separate_tracks: bool,
+    pub noise_suppression: bool,
+    pub loudness_target: Option<f32>, // LUFS, None to skip normalization
+    pub overlay: Option<OverlayConfig>,
+    pub watermark: Option<WatermarkConfig>,
+    pub sidecar: bool, // write recording details as JSON next to the video
+    pub work_dir: PathBuf, // segments and intermediate files
+    pub min_free: u64, // bytes the working folder needs free to start
+    pub final_path: String,
+}
+
+// Names given to the loopback sink-inputs so their volume can be found
+// again, suffixed with the session name
+const MIC_LOOPBACK: &str = "roton-mic";
+const MONITOR_LOOPBACK: &str = "roton-monitor";
+// Slider drags send a burst of changes, only the last one is applied
+const MIX_DEBOUNCE: Duration = Duration::from_millis(50);
+
+/// Gain and mute state for the "Both" mixer loopbacks
+#[derive(Clone, Copy)]
+struct MixLevels {
+    mic_volume: u32,
+    monitor_volume: u32,
+    mic_muted: bool,
+}
+
+// One wl-screenrec run, with the files captured next to it
+struct Segment {
+    video: PathBuf,
+    monitor_audio: Option<PathBuf>,
+    overlay: Option<PathBuf>,
+    source: CaptureSource,
+    started: Instant,
+    started_at: DateTime<Local>,
+    length: Option<Duration>, // wall clock, set when the segment stops
+    markers: Vec<Duration>,   // offsets into this segment
+}
+
+impl Segment {
+    fn files(&self) -> impl Iterator<Item = &PathBuf> {
+        std::iter::once(&self.video).chain(&self.monitor_audio).chain(&self.overlay)
+    }
 }
 
 pub struct Recorder {
     process: Option<Child>,
+    audio_process: Option<Child>,
+    overlay_process: Option<Child>,
     pulse_modules: Vec<String>,
+    mix_levels: Option<Sender<MixLevels>>, // to the thread applying them, while the mixer is loaded
     config: Option<RecordingConfig>,
-    temp_segments: Vec<PathBuf>,
+    segments: Vec<Segment>,
     is_paused: bool,
+    mic_volume: u32,
+    monitor_volume: u32,
+    mic_muted: bool,
+    last_loudness: Option<LoudnessStats>,
+    last_chapters: Vec<Chapter>,
+    last_output: Option<PathBuf>,
+    last_skipped: Vec<String>, // post-processing that failed on the last recording
+    session: String, // keeps sinks and files apart from other instances
 }
 
 impl Recorder {
     pub fn new() -> Self {
         Self { 
             process: None,
+            audio_process: None,
+            overlay_process: None,
             pulse_modules: Vec::new(),
+            mix_levels: None,
             config: None,
-            temp_segments: Vec::new(),
+            segments: Vec::new(),
             is_paused: false,
+            mic_volume: 100,
+            monitor_volume: 100,
+            mic_muted: false,
+            last_loudness: None,
+            last_chapters: Vec::new(),
+            last_output: None,
+            last_skipped: Vec::new(),
+            session: instance::session_name(),
         }
     }
 
-    pub fn is_installed(cmd: &str) -> bool {
-        Command::new("which")
-            .arg(cmd)
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .status()
-            .map(|s| s.success())
-            .unwrap_or(false)
-    }
-
-    pub fn is_available() -> bool {
-        Self::is_installed("wl-screenrec")
-    }
-
     // PulseAudio Helper Methods
-    fn load_pulse_module(&mut self, args: &[&str]) -> Option<String> {
-        let output = Command::new("pactl")
-            .arg("load-module")
-            .args(args)
-            .output()
-            .ok()?;
+    fn load_pulse_module(&mut self, args: &[&str]) -> Result<String, RecorderError> {
+        let mut cmd = Command::new("pactl");
+        cmd.arg("load-module").args(args);
+        logging::command(&cmd);
+        let output = cmd.output().map_err(|e| RecorderError::spawn("pactl", e))?;
+        logging::output("pactl", &output);
 
         if output.status.success() {
             let id = String::from_utf8_lossy(&output.stdout).trim().to_string();
             self.pulse_modules.push(id.clone());
-            Some(id)
+            Ok(id)
         } else {
-            None
+            Err(RecorderError::AudioModule {
+                module: args.first().copied().unwrap_or_default().to_string(),
+                stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+            })
         }
     }
 
     fn unload_pulse_modules(&mut self) {
+        // Stops the mix level thread
+        self.mix_levels = None;
         for id in &self.pulse_modules {
-            let _ = Command::new("pactl")
-                .arg("unload-module")
-                .arg(id)
-                .status();
+            pactl(&["unload-module", id]);
         }
         self.pulse_modules.clear();
     }
 
-    // Internal method to start a single segment recording
-    fn start_segment(&mut self) -> Result<(), String> {
-        if let Some(config) = &self.config {
-            // Generate temp file path in system temp dir
-            let timestamp = chrono::Local::now().format("%H-%M-%S-%f");
-            let temp_file = std::env::temp_dir().join(format!("roton_seg_{}.mp4", timestamp));
-            let temp_path_str = temp_file.to_str().unwrap().to_string();
-
-            let mut cmd = Command::new("wl-screenrec");
-            cmd.arg("-f").arg(&temp_path_str);
-
-            if let Some(geo) = &config.geometry {
-                cmd.arg("-g").arg(geo);
+    // Find the sink-input created by one of our loopback modules
+    fn find_sink_input(media_name: &str) -> Option<String> {
+        let output = pactl(&["list", "sink-inputs"])?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        let needle = format!("media.name = \"{}\"", media_name);
+        let mut current_id = None;
+        for line in stdout.lines() {
+            if let Some(id) = line.strip_prefix("Sink Input #") {
+                current_id = Some(id.trim().to_string());
+            } else if line.trim() == needle {
+                return current_id;
+            }
+        }
+        None
+    }
+
+    // The null sink mixing mic and monitor in "Both" mode
+    fn mixer_sink(&self) -> String {
+        format!("RotonMixer-{}", self.session)
+    }
+
+    fn loopback_name(&self, base: &str) -> String {
+        format!("{}-{}", base, self.session)
+    }
+
+    // Apply mix levels to the loopbacks from a thread of their own, so
+    // pactl never runs on the UI thread. The sink-inputs are looked up once.
+    fn start_mix_thread(&mut self) {
+        let (sender, receiver) = mpsc::channel::<MixLevels>();
+        let (mic_name, monitor_name) = (self.loopback_name(MIC_LOOPBACK), self.loopback_name(MONITOR_LOOPBACK));
+        std::thread::spawn(move || {
+            let mic = Self::find_sink_input(&mic_name);
+            let monitor = Self::find_sink_input(&monitor_name);
+            if mic.is_none() || monitor.is_none() {
+                warn!("Could not find the mixer loopbacks, mix levels won't apply");
             }
 
-            match config.audio_mode.as_str() {
-                "Screen" => {
-                    cmd.arg("--audio");
-                    if let Some(dev) = &config.monitor_device {
-                        cmd.arg("--audio-device").arg(dev);
-                    }
+            while let Ok(mut levels) = receiver.recv() {
+                std::thread::sleep(MIX_DEBOUNCE);
+                while let Ok(newer) = receiver.try_recv() {
+                    levels = newer;
                 }
-                "Mic" => {
-                    cmd.arg("--audio");
-                    if let Some(dev) = &config.mic_device {
-                        cmd.arg("--audio-device").arg(dev);
-                    }
+                if let Some(id) = &mic {
+                    pactl(&["set-sink-input-volume", id, &format!("{}%", levels.mic_volume)]);
+                    pactl(&["set-sink-input-mute", id, if levels.mic_muted { "1" } else { "0" }]);
                 }
-                "Both" => {
-                    // Use the ALREADY created virtual mixer if possible, 
-                    // or rely on the mixer created at start_session.
-                    // Since modules are persistent in `pulse_modules`, we just point to the sink monitor.
-                     cmd.arg("--audio");
-                     cmd.arg("--audio-device").arg("RotonMixer.monitor");
+                if let Some(id) = &monitor {
+                    pactl(&["set-sink-input-volume", id, &format!("{}%", levels.monitor_volume)]);
                 }
-                _ => {}
             }
+        });
+        self.mix_levels = Some(sender);
+    }
+
+    // Push the current gain/mute state to the "Both" mixer loopbacks, if any
+    fn apply_mix_levels(&self) {
+        if let Some(sender) = &self.mix_levels {
+            let _ = sender.send(MixLevels {
+                mic_volume: self.mic_volume,
+                monitor_volume: self.monitor_volume,
+                mic_muted: self.mic_muted,
+            });
+        }
+    }
+
+    // Internal method to start a single segment recording
+    fn start_segment(&mut self) -> Result<(), RecorderError> {
+        let Some(config) = &self.config else {
+            return Err(RecorderError::NoSession);
+        };
+        let source = config.source.clone();
+
+        // Generate temp file path in the working directory
+        let timestamp = chrono::Local::now().format("%H-%M-%S-%f");
+        let temp_file = config.work_dir.join(format!("roton_seg_{}.mp4", timestamp));
+        let temp_path_str = temp_file.to_str().unwrap().to_string();
+
+        let mut cmd = Command::new("wl-screenrec");
+        cmd.arg("-f").arg(&temp_path_str);
+
+        if let Some(geo) = &source.geometry {
+            cmd.arg("-g").arg(geo.to_string());
+        } else if let Some(output) = &source.output {
+            cmd.arg("-o").arg(output);
+        }
+
+        match source.audio_mode.as_str() {
+            "Screen" => {
+                cmd.arg("--audio");
+                if let Some(dev) = &source.monitor_device {
+                    cmd.arg("--audio-device").arg(dev);
+                }
+            }
+            "Mic" => {
+                cmd.arg("--audio");
+                if let Some(dev) = &source.mic_device {
+                    cmd.arg("--audio-device").arg(dev);
+                }
+            }
+            "Both" if config.separate_tracks => {
+                // Mic goes into the video file, the monitor is captured
+                // alongside and muxed in as a second track on finish.
+                cmd.arg("--audio");
+                if let Some(dev) = &source.mic_device {
+                    cmd.arg("--audio-device").arg(dev);
+                }
+            }
+            "Both" => {
+                // Use the ALREADY created virtual mixer if possible, 
+                // or rely on the mixer created at start_session.
+                // Since modules are persistent in `pulse_modules`, we just point to the sink monitor.
+                 cmd.arg("--audio");
+                 cmd.arg("--audio-device").arg(format!("{}.monitor", self.mixer_sink()));
+            }
+            _ => {}
+        }
+
+        let separate_monitor = if source.audio_mode == "Both" && config.separate_tracks {
+            source.monitor_device.clone()
+        } else {
+            None
+        };
+        // Camera overlays are recorded per segment so they line up after pauses
+        let overlay_device = config.overlay.as_ref()
+            .filter(|o| o.is_device())
+            .map(|o| o.source.clone());
+
+        cmd.stderr(Stdio::piped());
+        logging::command(&cmd);
+        match cmd.spawn() {
+            Ok(mut child) => {
+                logging::follow_stderr("wl-screenrec", &mut child);
+                info!("Started segment: {:?}", temp_file);
+                self.process = Some(child);
+            }
+            Err(e) => return Err(RecorderError::spawn("wl-screenrec", e)),
+        }
+
+        let mut segment = Segment {
+            video: temp_file,
+            monitor_audio: None,
+            overlay: None,
+            source,
+            started: Instant::now(),
+            started_at: Local::now(),
+            length: None,
+            markers: Vec::new(),
+        };
+
+        if let Some(monitor) = separate_monitor {
+            let audio_file = config.work_dir.join(format!("roton_seg_{}_monitor.m4a", timestamp));
+            let mut cmd = Command::new("ffmpeg");
+            cmd.arg("-nostats").arg("-loglevel").arg("warning")
+                .arg("-f").arg("pulse")
+                .arg("-i").arg(&monitor)
+                .arg("-c:a").arg("aac")
+                .arg("-y")
+                .arg(&audio_file)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::piped());
+            logging::command(&cmd);
 
             match cmd.spawn() {
+                Ok(mut child) => {
+                    logging::follow_stderr("ffmpeg", &mut child);
+                    info!("Started audio segment: {:?}", audio_file);
+                    self.audio_process = Some(child);
+                    segment.monitor_audio = Some(audio_file);
+                }
+                Err(e) => {
+                    self.discard_segment(segment);
+                    return Err(RecorderError::spawn("ffmpeg", e));
+                }
+            }
+        }
+
+        if let Some(device) = overlay_device {
+            let overlay_file = config.work_dir.join(format!("roton_seg_{}_overlay.mkv", timestamp));
+            match overlay::start_capture(&device, overlay_file.to_str().unwrap()) {
                 Ok(child) => {
-                    println!("Started segment: {:?}", temp_file);
-                    self.process = Some(child);
-                    self.temp_segments.push(temp_file);
-                    Ok(())
+                    info!("Started overlay segment: {:?}", overlay_file);
+                    self.overlay_process = Some(child);
+                    segment.overlay = Some(overlay_file);
+                }
+                Err(e) => {
+                    self.discard_segment(segment);
+                    return Err(RecorderError::Ffmpeg { step: "camera capture".to_string(), stderr: e });
                 }
-                Err(e) => Err(format!("Failed to start segment: {}", e)),
             }
-        } else {
-            Err("No configuration found".to_string())
+        }
+
+        self.segments.push(segment);
+        Ok(())
+    }
+
+    // Stop a segment that failed to start completely and drop its files, so
+    // every kept segment has all of its parts
+    fn discard_segment(&mut self, segment: Segment) {
+        self.stop_current_process();
+        for path in segment.files() {
+            let _ = fs::remove_file(path);
         }
     }
 
     fn stop_current_process(&mut self) {
-        if let Some(mut child) = self.process.take() {
-            let pid = child.id();
-            let _ = Command::new("kill").arg("-s").arg("INT").arg(pid.to_string()).status();
-            let _ = child.wait();
+        if self.process.is_some() {
+            if let Some(segment) = self.segments.last_mut() {
+                segment.length.get_or_insert(segment.started.elapsed());
+            }
+        }
+        for child in [self.process.take(), self.audio_process.take(), self.overlay_process.take()].into_iter().flatten() {
+            Self::interrupt(child);
+        }
+    }
+
+    // SIGINT lets wl-screenrec and ffmpeg finalize their output files
+    fn interrupt(mut child: Child) {
+        let pid = child.id();
+        let _ = Command::new("kill").arg("-s").arg("INT").arg(pid.to_string()).status();
+        let _ = child.wait();
+    }
+
+    fn move_file(from: &Path, to: &str) -> Result<(), RecorderError> {
+        // Try rename first, fallback to copy if cross-device (tmpfs to disk)
+        if let Err(e) = fs::rename(from, to) {
+            if e.raw_os_error() == Some(18) { // EXDEV: Invalid cross-device link
+                fs::copy(from, to)?;
+                fs::remove_file(from)?;
+            } else {
+                return Err(e.into());
+            }
+        }
+        Ok(())
+    }
+
+    // Join segments, or just move a lone segment into place. Segments are probed
+    // first: matching ones are stream copied, anything else (output or scale
+    // changed between pause and resume, a device with another sample rate) is
+    // re-encoded so the result always plays.
+    fn concat_segments(segments: &[PathBuf], output: &str) -> Result<(), RecorderError> {
+        if segments.len() == 1 {
+            return Self::move_file(&segments[0], output);
+        }
+
+        let infos = match segments.iter().map(|s| probe::probe(s)).collect::<Result<Vec<_>, _>>() {
+            Ok(infos) => infos,
+            Err(e) => {
+                warn!("Could not probe segments, assuming they match: {}", e);
+                return Self::concat_copy(segments, output);
+            }
+        };
+
+        if !probe::can_stream_copy(&infos) {
+            info!("Segments differ, re-encoding to join them");
+            return Self::concat_reencode(segments, &infos, output);
+        }
+        Self::concat_copy(segments, output).or_else(|e| {
+            warn!("Stream copy failed, re-encoding instead: {}", e);
+            Self::concat_reencode(segments, &infos, output)
+        })
+    }
+
+    // Join segments with ffmpeg's concat demuxer without re-encoding
+    fn concat_copy(segments: &[PathBuf], output: &str) -> Result<(), RecorderError> {
+        // 1. Create list.txt next to the output
+        let list_path = Path::new(output).with_file_name("roton_concat_list.txt");
+        let mut list_content = String::new();
+        for path in segments {
+             list_content.push_str(&format!("file '{}'\n", path.to_str().unwrap()));
+        }
+        fs::write(&list_path, list_content)?;
+
+        // 2. Run FFMPEG Concat
+        info!("Concatenating to: {}", output);
+        let mut cmd = Command::new("ffmpeg");
+        cmd.arg("-f").arg("concat")
+            .arg("-safe").arg("0")
+            .arg("-i").arg(&list_path)
+            .arg("-c").arg("copy")
+            .arg("-y") // overwrite
+            .arg(output)
+            .stdin(Stdio::null());
+        logging::command(&cmd);
+        let output = cmd.output().map_err(|e| RecorderError::spawn("ffmpeg", e))?;
+        logging::output("ffmpeg", &output);
+
+        // Cleanup temp list
+        let _ = fs::remove_file(list_path);
+
+        if !output.status.success() {
+            return Err(RecorderError::Concat { stderr: String::from_utf8_lossy(&output.stderr).to_string() });
+        }
+        Ok(())
+    }
+
+    // Join segments with the concat filter, scaling and padding every video to
+    // the first segment's size and resampling audio to 48 kHz stereo. Segments
+    // without audio get silence so the tracks stay in sync.
+    fn concat_reencode(segments: &[PathBuf], infos: &[MediaInfo], output: &str) -> Result<(), RecorderError> {
+        let size = infos.iter()
+            .filter_map(|i| i.video())
+            .find_map(|v| Some((v.width? & !1, v.height? & !1)))
+            .filter(|&(w, h)| w > 0 && h > 0);
+        let has_audio = infos.iter().any(|i| i.audio().is_some());
+
+        let mut cmd = Command::new("ffmpeg");
+        let mut filter = String::new();
+        let mut pads = String::new();
+        for (i, (segment, info)) in segments.iter().zip(infos).enumerate() {
+            cmd.arg("-i").arg(segment);
+
+            if let Some((w, h)) = size {
+                if info.video().is_none() {
+                    return Err(RecorderError::Concat {
+                        stderr: format!("{} has no video stream", segment.display()),
+                    });
+                }
+                filter.push_str(&format!(
+                    "[{i}:v:0]scale={w}:{h}:force_original_aspect_ratio=decrease,pad={w}:{h}:(ow-iw)/2:(oh-ih)/2,setsar=1,format=yuv420p[v{i}];"
+                ));
+                pads.push_str(&format!("[v{i}]"));
+            }
+            if has_audio {
+                if info.audio().is_some() {
+                    filter.push_str(&format!("[{i}:a:0]aresample=48000,aformat=channel_layouts=stereo[a{i}];"));
+                } else {
+                    filter.push_str(&format!(
+                        "anullsrc=r=48000:cl=stereo,atrim=duration={:.3}[a{i}];",
+                        info.duration
+                    ));
+                }
+                pads.push_str(&format!("[a{i}]"));
+            }
+        }
+
+        filter.push_str(&format!(
+            "{pads}concat=n={}:v={}:a={}{}{}",
+            segments.len(),
+            size.is_some() as u8,
+            has_audio as u8,
+            if size.is_some() { "[v]" } else { "" },
+            if has_audio { "[a]" } else { "" },
+        ));
+        cmd.arg("-filter_complex").arg(filter);
+        if size.is_some() {
+            cmd.arg("-map").arg("[v]")
+                .arg("-c:v").arg("libx264")
+                .arg("-preset").arg("veryfast")
+                .arg("-crf").arg("20");
+        }
+        if has_audio {
+            cmd.arg("-map").arg("[a]")
+                .arg("-c:a").arg("aac");
+        }
+        cmd.arg("-y")
+            .arg(output)
+            .stdin(Stdio::null());
+
+        info!("Re-encoding {} segments to: {}", segments.len(), output);
+        logging::command(&cmd);
+        let result = cmd.output().map_err(|e| RecorderError::spawn("ffmpeg", e))?;
+        logging::output("ffmpeg", &result);
+
+        if !result.status.success() {
+            return Err(RecorderError::Concat { stderr: String::from_utf8_lossy(&result.stderr).to_string() });
+        }
+        Ok(())
+    }
+
+    // Where every segment lands in the joined file, from the probed segment
+    // lengths so chapters line up with the result
+    fn timings(&self) -> Vec<SegmentTiming> {
+        let mut offset = 0.0;
+        self.segments
+            .iter()
+            .map(|segment| {
+                let duration = probe::probe(&segment.video)
+                    .map(|info| info.duration)
+                    .ok()
+                    .filter(|&d| d > 0.0)
+                    .unwrap_or_else(|| segment.length.unwrap_or_default().as_secs_f64());
+                let timing = SegmentTiming {
+                    started: segment.started_at.to_rfc3339(),
+                    offset,
+                    duration,
+                    markers: segment.markers.iter().map(|m| offset + m.as_secs_f64().min(duration)).collect(),
+                    source: segment.source.clone(),
+                };
+                offset += duration;
+                timing
+            })
+            .collect()
+    }
+
+    // A chapter at every resume and every marker
+    fn chapters(timings: &[SegmentTiming]) -> Vec<Chapter> {
+        let boundaries: Vec<f64> = timings
+            .iter()
+            .enumerate()
+            .flat_map(|(i, t)| (i > 0).then_some(t.offset).into_iter().chain(t.markers.iter().copied()))
+            .collect();
+        let total = timings.last().map(|t| t.offset + t.duration).unwrap_or_default();
+        chapters::from_boundaries(&boundaries, total)
+    }
+
+    // Silence as long as a segment's video, standing in for a missing track
+    fn silent_track(video: &Path, output: &Path) -> Result<(), RecorderError> {
+        let duration = probe::probe(video)
+            .map_err(|e| RecorderError::Ffmpeg { step: "segment probe".to_string(), stderr: e })?
+            .duration;
+
+        let mut cmd = Command::new("ffmpeg");
+        cmd.arg("-f").arg("lavfi")
+            .arg("-i").arg("anullsrc=r=48000:cl=stereo")
+            .arg("-t").arg(format!("{:.3}", duration))
+            .arg("-c:a").arg("aac")
+            .arg("-y")
+            .arg(output)
+            .stdin(Stdio::null());
+        logging::command(&cmd);
+        let result = cmd.output().map_err(|e| RecorderError::spawn("ffmpeg", e))?;
+        logging::output("ffmpeg", &result);
+
+        if !result.status.success() {
+            return Err(RecorderError::Ffmpeg {
+                step: "silent track".to_string(),
+                stderr: String::from_utf8_lossy(&result.stderr).to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    // Add the separately captured monitor audio as a second track
+    fn mux_audio_track(video: &str, audio: &str, output: &str) -> Result<(), RecorderError> {
+        info!("Muxing separate audio track into: {}", output);
+        let mut cmd = Command::new("ffmpeg");
+        cmd.arg("-i").arg(video)
+            .arg("-i").arg(audio)
+            .arg("-map").arg("0")
+            .arg("-map").arg("1:a")
+            .arg("-c").arg("copy")
+            .arg("-shortest")
+            .arg("-metadata:s:a:0").arg("title=Microphone")
+            .arg("-metadata:s:a:1").arg("title=System Audio")
+            .arg("-y")
+            .arg(output)
+            .stdin(Stdio::null());
+        logging::command(&cmd);
+        let result = cmd.output().map_err(|e| RecorderError::spawn("ffmpeg", e))?;
+        logging::output("ffmpeg", &result);
+
+        if !result.status.success() {
+            return Err(RecorderError::Ffmpeg {
+                step: "audio track mux".to_string(),
+                stderr: String::from_utf8_lossy(&result.stderr).to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    // Run an optional post-processing step on the staged file. A failed step is
+    // skipped so the recording itself is never lost to a filter error, and
+    // noted in `skipped` under `label` for the finished page.
+    fn run_stage<F>(staged: &mut PathBuf, name: &str, label: &str, skipped: &mut Vec<String>, stage: F)
+    where
+        F: FnOnce(&str, &str) -> Result<(), String>,
+    {
+        let next = staged.with_file_name(name);
+        match stage(staged.to_str().unwrap(), next.to_str().unwrap()) {
+            Ok(()) => {
+                let _ = fs::remove_file(&*staged);
+                *staged = next;
+            }
+            Err(e) => {
+                warn!("Skipping {}: {}", name, e);
+                skipped.push(format!("{} skipped: {}", label, e));
+                let _ = fs::remove_file(next);
+            }
+        }
+    }
+
+    // Denoise the first audio track, which only holds the mic, with afftdn
+    fn denoise_mic_track(input: &str, output: &str) -> Result<(), String> {
+        info!("Applying noise suppression");
+        let mut cmd = Command::new("ffmpeg");
+        cmd.arg("-i").arg(input)
+            .arg("-map").arg("0")
+            .arg("-c").arg("copy")
+            .arg("-filter:a:0").arg("afftdn=nf=-25")
+            .arg("-c:a:0").arg("aac")
+            .arg("-y")
+            .arg(output)
+            .stdin(Stdio::null());
+        logging::command(&cmd);
+        let result = cmd.output().map_err(|e| e.to_string())?;
+        logging::output("ffmpeg", &result);
+
+        if !result.status.success() {
+            return Err("FFmpeg noise suppression failed".to_string());
         }
+        Ok(())
+    }
+
+    // Route mic and monitor through the RotonMixer sink for "Both" (not used
+    // when keeping tracks apart)
+    // "Both" can't fall back to a default device: the mixer needs both
+    // sources, and the second track would otherwise be left out silently
+    fn check_devices(source: &CaptureSource) -> Result<(), RecorderError> {
+        if source.audio_mode != "Both" {
+            return Ok(());
+        }
+        if source.mic_device.is_none() {
+            return Err(RecorderError::MissingDevice("mic".to_string()));
+        }
+        if source.monitor_device.is_none() {
+            return Err(RecorderError::MissingDevice("monitor".to_string()));
+        }
+        Ok(())
+    }
+
+    fn uses_mixer(source: &CaptureSource, separate_tracks: bool) -> bool {
+        source.audio_mode == "Both" && !separate_tracks && source.mic_device.is_some() && source.monitor_device.is_some()
+    }
+
+    fn setup_mixer(&mut self, source: &CaptureSource, separate_tracks: bool) -> Result<(), RecorderError> {
+        if Self::uses_mixer(source, separate_tracks) {
+             if let (Some(m), Some(mon)) = (&source.mic_device, &source.monitor_device) {
+                // Setup Mixer
+                let sink = self.mixer_sink();
+                let (mic_name, monitor_name) = (self.loopback_name(MIC_LOOPBACK), self.loopback_name(MONITOR_LOOPBACK));
+                let mixer = self.load_pulse_module(&["module-null-sink", &format!("sink_name={}", sink), &format!("sink_properties=device.description={}", sink)])
+                    .and_then(|_| self.load_pulse_module(&["module-loopback", &format!("sink={}", sink), &format!("source={}", m), "latency_msec=1", &format!("sink_input_properties=media.name={}", mic_name)]))
+                    .and_then(|_| self.load_pulse_module(&["module-loopback", &format!("sink={}", sink), &format!("source={}", mon), "latency_msec=1", &format!("sink_input_properties=media.name={}", monitor_name)]));
+                if let Err(e) = mixer {
+                    // Don't leave a half-built mixer behind
+                    self.unload_pulse_modules();
+                    return Err(e);
+                }
+                self.start_mix_thread();
+                self.apply_mix_levels();
+             }
+        }
+        Ok(())
     }
 
     // Public API
 
-    pub fn start_session(&mut self, final_path: &str, geometry: Option<&str>, audio_mode: &str, mic: Option<&str>, monitor: Option<&str>) -> Result<(), String> {
+    pub fn start_session(&mut self, mut config: RecordingConfig) -> Result<(), RecorderError> {
         // Clear previous session state
         self.stop_current_process();
         self.unload_pulse_modules();
-        self.temp_segments.clear();
+        self.segments.clear();
         self.is_paused = false;
+        self.mic_muted = false;
+        self.session = instance::session_name();
 
-        // Setup PulseAudio mixer if needed for "Both"
-        if audio_mode == "Both" {
-             if let (Some(m), Some(mon)) = (mic, monitor) {
-                // Setup Mixer
-                self.load_pulse_module(&["module-null-sink", "sink_name=RotonMixer", "sink_properties=device.description=RotonMixer"]);
-                self.load_pulse_module(&["module-loopback", "sink=RotonMixer", &format!("source={}", m), "latency_msec=1"]);
-                self.load_pulse_module(&["module-loopback", "sink=RotonMixer", &format!("source={}", mon), "latency_msec=1"]);
-             }
+        // Every session gets its own folder, so two instances sharing a save
+        // folder never touch each other's segments
+        config.work_dir = config.work_dir.join(format!("session-{}", self.session));
+        fs::create_dir_all(&config.work_dir)?;
+        match disk::available(&config.work_dir) {
+            Ok(available) if available < config.min_free => {
+                return Err(RecorderError::LowDiskSpace { path: config.work_dir.clone(), available, required: config.min_free });
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Could not check free space: {}", e),
         }
 
+        Self::check_devices(&config.source)?;
+        self.setup_mixer(&config.source, config.separate_tracks)?;
+
         // Save Config
-        self.config = Some(RecordingConfig {
-            geometry: geometry.map(|s| s.to_string()),
-            audio_mode: audio_mode.to_string(),
-            mic_device: mic.map(|s| s.to_string()),
-            monitor_device: monitor.map(|s| s.to_string()),
-            final_path: final_path.to_string(),
-        });
+        self.config = Some(config);
 
         // Start first segment
-        self.start_segment()
+        let result = self.start_segment();
+        if result.is_err() {
+            self.unload_pulse_modules();
+        }
+        result
+    }
+
+    /// Switch what the next segment captures. Only possible while paused,
+    /// since every segment is a separate wl-screenrec run.
+    pub fn change_source(&mut self, source: CaptureSource) -> Result<(), RecorderError> {
+        if !self.is_paused {
+            return Err(RecorderError::NotPaused);
+        }
+        let Some(config) = &self.config else {
+            return Err(RecorderError::NoSession);
+        };
+        let separate_tracks = config.separate_tracks;
+        // A failed change can leave the old source without its mixer
+        let mixer_missing = Self::uses_mixer(&source, separate_tracks) && self.pulse_modules.is_empty();
+        if config.source == source && !mixer_missing {
+            return Ok(());
+        }
+        let previous = config.source.clone();
+        Self::check_devices(&source)?;
+
+        // The mixer's loopbacks are tied to the old devices, so rebuild it
+        info!("Changing capture source for the next segment");
+        self.unload_pulse_modules();
+        if let Err(e) = self.setup_mixer(&source, separate_tracks) {
+            // Keep recording what was recorded before
+            if let Err(restore) = self.setup_mixer(&previous, separate_tracks) {
+                error!("Could not restore the previous mixer: {}", restore);
+            }
+            return Err(e);
+        }
+        if let Some(config) = self.config.as_mut() {
+            config.source = source;
+        }
+        Ok(())
+    }
+
+    pub fn set_mic_volume(&mut self, percent: u32) {
+        self.mic_volume = percent;
+        self.apply_mix_levels();
+    }
+
+    pub fn set_monitor_volume(&mut self, percent: u32) {
+        self.monitor_volume = percent;
+        self.apply_mix_levels();
+    }
+
+    pub fn set_mic_muted(&mut self, muted: bool) {
+        self.mic_muted = muted;
+        self.apply_mix_levels();
+    }
+
+    /// Loudness of the last finished recording, if it was normalized
+    pub fn last_loudness(&self) -> Option<LoudnessStats> {
+        self.last_loudness
+    }
+
+    /// Chapters written to the last finished recording
+    pub fn last_chapters(&self) -> Vec<Chapter> {
+        self.last_chapters.clone()
+    }
+
+    /// Post-processing steps that failed on the last finished recording
+    pub fn last_skipped(&self) -> Vec<String> {
+        self.last_skipped.clone()
+    }
+
+    /// Where the last finished recording was saved
+    pub fn last_output(&self) -> Option<PathBuf> {
+        self.last_output.clone()
+    }
+
+    /// Free space left for the running session. None without a session or
+    /// when it can't be checked.
+    pub fn disk_usage(&self) -> Option<DiskUsage> {
+        let config = self.config.as_ref()?;
+        let available = disk::available(&config.work_dir)
+            .map_err(|e| warn!("Could not check free space: {}", e))
+            .ok()?;
+        // Joining the segments writes about as much again
+        let reserved = self.segments.iter()
+            .flat_map(Segment::files)
+            .filter_map(|path| fs::metadata(path).ok())
+            .map(|m| m.len())
+            .sum();
+        Some(DiskUsage { available, reserved })
+    }
+
+    /// Start a new chapter at the current position. Returns how many markers
+    /// the session has. Pausing already starts a chapter, so this does nothing
+    /// while paused.
+    pub fn add_marker(&mut self) -> Result<usize, RecorderError> {
+        if self.config.is_none() {
+            return Err(RecorderError::NoSession);
+        }
+        if !self.is_paused {
+            if let Some(segment) = self.segments.last_mut() {
+                segment.markers.push(segment.started.elapsed());
+                info!("Added marker at {:?} into segment {}", segment.started.elapsed(), self.segments.len());
+            }
+        }
+        Ok(self.segments.iter().map(|s| s.markers.len()).sum())
     }
 
-    pub fn pause_session(&mut self) -> Result<(), String> {
+    pub fn pause_session(&mut self) -> Result<(), RecorderError> {
         if !self.is_paused {
             self.stop_current_process();
             self.is_paused = true;
-            println!("Session paused.");
+            info!("Session paused.");
         }
         Ok(())
     }
 
-    pub fn resume_session(&mut self) -> Result<(), String> {
+    pub fn resume_session(&mut self) -> Result<(), RecorderError> {
         if self.is_paused {
             self.start_segment()?;
             self.is_paused = false;
-            println!("Session resumed.");
+            info!("Session resumed.");
         }
         Ok(())
     }
 
-    pub fn finish_session(&mut self) -> Result<(), String> {
+    pub fn finish_session(&mut self) -> Result<(), RecorderError> {
         self.stop_current_process();
         self.unload_pulse_modules();
 
-        if self.temp_segments.is_empty() {
-            return Err("No recordings made".to_string());
+        if self.segments.is_empty() {
+            return Err(RecorderError::NoSegments);
         }
 
-        let final_path = if let Some(cfg) = &self.config {
-            cfg.final_path.clone()
+        let mut config = if let Some(cfg) = &self.config {
+            cfg.clone()
         } else {
-            return Err("Config lost".to_string());
+            return Err(RecorderError::NoSession);
         };
 
-        println!("Finishing session. Segments: {}", self.temp_segments.len());
+        info!("Finishing session. Segments: {}", self.segments.len());
 
-        if self.temp_segments.len() == 1 {
-            // Try rename first, fallback to copy if cross-device (tmpfs to disk)
-            if let Err(e) = fs::rename(&self.temp_segments[0], &final_path) {
-                if e.raw_os_error() == Some(18) { // EXDEV: Invalid cross-device link
-                    fs::copy(&self.temp_segments[0], &final_path).map_err(|e| e.to_string())?;
-                    fs::remove_file(&self.temp_segments[0]).map_err(|e| e.to_string())?;
-                } else {
-                    return Err(e.to_string());
+        // Work out timing while the segments can still be probed one by one
+        let timings = self.timings();
+        let session_chapters = Self::chapters(&timings);
+        let started = self.segments[0].started_at;
+
+        // Assemble everything in the working dir first, post-process, then move into place
+        let mut staged = config.work_dir.join("roton_assembled.mp4");
+        let staged_str = staged.to_str().unwrap().to_string();
+
+        let videos: Vec<PathBuf> = self.segments.iter().map(|s| s.video.clone()).collect();
+        if self.segments.iter().all(|s| s.monitor_audio.is_none()) {
+            Self::concat_segments(&videos, &staged_str)?;
+        } else {
+            let video_path = config.work_dir.join("roton_video.mp4");
+            let audio_path = config.work_dir.join("roton_monitor.m4a");
+            let video_str = video_path.to_str().unwrap().to_string();
+            let audio_str = audio_path.to_str().unwrap().to_string();
+
+            // Segments recorded in another audio mode get silence on the
+            // second track so it stays in sync with the video
+            let mut fillers = Vec::new();
+            let mut tracks = Vec::new();
+            let mut result = Ok(());
+            for (i, segment) in self.segments.iter().enumerate() {
+                match &segment.monitor_audio {
+                    Some(path) => tracks.push(path.clone()),
+                    None => {
+                        let filler = config.work_dir.join(format!("roton_silence_{}.m4a", i));
+                        result = Self::silent_track(&segment.video, &filler);
+                        fillers.push(filler.clone());
+                        tracks.push(filler);
+                        if result.is_err() {
+                            break;
+                        }
+                    }
                 }
             }
-        } else {
-            // Concat multiple files
-            // 1. Create list.txt
-            let list_path = std::env::temp_dir().join("roton_concat_list.txt");
-            let mut list_content = String::new();
-            for path in &self.temp_segments {
-                 list_content.push_str(&format!("file '{}'\n", path.to_str().unwrap()));
+
+            let result = result
+                .and_then(|_| Self::concat_segments(&videos, &video_str))
+                .and_then(|_| Self::concat_segments(&tracks, &audio_str))
+                .and_then(|_| Self::mux_audio_track(&video_str, &audio_str, &staged_str));
+
+            for path in fillers.iter().chain([&video_path, &audio_path]) {
+                let _ = fs::remove_file(path);
             }
-            fs::write(&list_path, list_content).map_err(|e| e.to_string())?;
-
-            // 2. Run FFMPEG Concat
-            println!("Concatenating to: {}", final_path);
-            let status = Command::new("ffmpeg")
-                .arg("-f").arg("concat")
-                .arg("-safe").arg("0")
-                .arg("-i").arg(&list_path)
-                .arg("-c").arg("copy")
-                .arg("-y") // overwrite
-                .arg(&final_path)
-                .stdout(Stdio::null())
-                .stderr(Stdio::null()) // maybe log stderr?
-                .status()
-                .map_err(|e| e.to_string())?;
+            result?;
+        }
 
-            if !status.success() {
-                return Err("FFmpeg concat failed".to_string());
+        // Overlay and watermark share one re-encode. A camera is recorded
+        // per segment, so its segments are joined first.
+        let mut skipped = Vec::new();
+        let camera_path = config.work_dir.join("roton_overlay.mkv");
+        let overlay = config.overlay.as_ref().and_then(|overlay_config| {
+            if !overlay_config.is_device() {
+                return Some((overlay_config, overlay_config.source.clone()));
             }
+            let cameras: Vec<PathBuf> = self.segments.iter().filter_map(|s| s.overlay.clone()).collect();
+            match Self::concat_segments(&cameras, camera_path.to_str().unwrap()) {
+                Ok(()) => Some((overlay_config, camera_path.to_string_lossy().to_string())),
+                Err(e) => {
+                    warn!("Skipping overlay: {}", e);
+                    skipped.push(format!("Overlay skipped: {}", e));
+                    None
+                }
+            }
+        });
+        if overlay.is_some() || config.watermark.is_some() {
+            let label = match (&overlay, &config.watermark) {
+                (Some(_), Some(_)) => "Overlay and watermark",
+                (Some(_), None) => "Overlay",
+                _ => "Watermark",
+            };
+            Self::run_stage(&mut staged, "roton_composed.mp4", label, &mut skipped, |input, output| {
+                let overlay = overlay.as_ref().map(|(c, source)| (*c, source.as_str()));
+                compose::apply(input, overlay, config.watermark.as_ref(), output)
+            });
+        }
+        let _ = fs::remove_file(&camera_path);
+
+        // The audio mode may have changed between segments
+        let has_mic = self.segments.iter().any(|s| s.source.has_mic());
+        let has_audio = self.segments.iter().any(|s| s.source.audio_mode != "Mute");
+        // afftdn runs on the whole first track, which must not carry system audio
+        let mic_alone = self.segments.iter().all(|s| match s.source.audio_mode.as_str() {
+            "Mic" | "Mute" => true,
+            "Both" => config.separate_tracks,
+            _ => false,
+        });
+        if config.noise_suppression && has_mic {
+            if mic_alone {
+                Self::run_stage(&mut staged, "roton_denoised.mp4", "Noise suppression", &mut skipped, Self::denoise_mic_track);
+            } else {
+                warn!("Skipping noise suppression, the mic shares its track with system audio");
+                skipped.push("Noise suppression skipped: the mic was mixed with system audio".to_string());
+            }
+        }
+
+        self.last_loudness = None;
+        if let (Some(target), true) = (config.loudness_target, has_audio) {
+            let mut stats = None;
+            Self::run_stage(&mut staged, "roton_normalized.mp4", "Loudness normalization", &mut skipped, |input, output| {
+                stats = Some(loudness::normalize(input, output, target)?);
+                Ok(())
+            });
+            self.last_loudness = stats;
+        }
 
-            // Cleanup temp list
-            let _ = fs::remove_file(list_path);
+        // Last, so no filter stage can drop tags or chapters
+        let tags = metadata::tags(started);
+        let mut written = false;
+        Self::run_stage(&mut staged, "roton_tagged.mp4", "Chapters and tags", &mut skipped, |input, output| {
+            chapters::write(input, &tags, &session_chapters, output)?;
+            written = true;
+            Ok(())
+        });
+        self.last_chapters = if written { session_chapters.clone() } else { Vec::new() };
+        self.last_skipped = skipped;
+
+        // The name may already be taken, by a recording from the same second
+        // or anything else, so record where it actually ended up
+        let saved = output::finalize(&staged, Path::new(&config.final_path), &self.session)?;
+        config.final_path = saved.to_string_lossy().to_string();
+        self.last_output = Some(saved.clone());
+
+        if config.sidecar {
+            if let Err(e) = metadata::write_sidecar(&saved, started, &config, &timings, &self.last_chapters) {
+                warn!("Skipping recording details: {}", e);
+            }
         }
 
         // Cleanup temp segments
-        for path in &self.temp_segments {
+        for path in self.segments.iter().flat_map(Segment::files) {
             let _ = fs::remove_file(path);
         }
+        let _ = fs::remove_dir(&config.work_dir);
         
         self.config = None;
-        self.temp_segments.clear();
+        self.segments.clear();
 
         Ok(())
     }
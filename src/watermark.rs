@@ -0,0 +1,59 @@
+use std::path::Path;
+
+use serde::Serialize;
+
+// Distance between the watermark and the edges of the frame
+const MARGIN: u32 = 20;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WatermarkContent {
+    Text { text: String, font: String, size: u32 },
+    Image(String),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WatermarkConfig {
+    pub content: WatermarkContent,
+    pub position: String, // "top-left", "top-right", "bottom-left" or "bottom-right"
+    pub opacity: u32,     // 0 - 100
+}
+
+impl WatermarkConfig {
+    // Filters name the frame and watermark sizes differently, so pass them in
+    fn position(&self, frame: (&str, &str), mark: (&str, &str)) -> String {
+        let x = if self.position.ends_with("right") { format!("{}-{}-{}", frame.0, mark.0, MARGIN) } else { MARGIN.to_string() };
+        let y = if self.position.starts_with("bottom") { format!("{}-{}-{}", frame.1, mark.1, MARGIN) } else { MARGIN.to_string() };
+        format!("x={}:y={}", x, y)
+    }
+
+    fn alpha(&self) -> f32 {
+        self.opacity.min(100) as f32 / 100.0
+    }
+}
+
+/// Filter drawing the watermark over the `base` stream, labelled `out`.
+/// Image watermarks come from input `index`, text is read from `text_file`
+/// so it needs no filter escaping.
+pub fn filter(config: &WatermarkConfig, base: &str, out: &str, index: usize, text_file: &Path) -> String {
+    match &config.content {
+        WatermarkContent::Text { font, size, .. } => format!(
+            "[{}]drawtext=textfile='{}':font='{}':fontsize={}:fontcolor=white@{alpha}:shadowcolor=black@{alpha}:shadowx=2:shadowy=2:{}[{}]",
+            base,
+            text_file.to_string_lossy(),
+            font.replace('\'', ""),
+            size,
+            config.position(("w", "h"), ("tw", "th")),
+            out,
+            alpha = config.alpha(),
+        ),
+        WatermarkContent::Image(_) => format!(
+            "[{}:v]format=rgba,colorchannelmixer=aa={}[wm];[{}][wm]overlay={}[{}]",
+            index,
+            config.alpha(),
+            base,
+            config.position(("W", "H"), ("w", "h")),
+            out,
+        ),
+    }
+}